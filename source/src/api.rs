@@ -0,0 +1,540 @@
+use {
+    crate::{
+        delete::delete,
+        error::Error,
+        flatten::{
+            flatten,
+            unflatten,
+            FlattenOptions,
+        },
+        force_array::force_array,
+        get::get,
+        infer_types::{
+            infer_datetimes,
+            infer_types,
+        },
+        intersect::intersect,
+        keep::keep,
+        merge::merge,
+        search_delete::search_delete,
+        search_set::search_set,
+        set::set,
+        subtract::subtract,
+        supervalue::Supervalue,
+        supervalue_path::DataPath,
+        utils::{
+            map_key_segment,
+            vec_index_segment,
+        },
+    },
+};
+
+/// Methods mirroring the CLI commands, for use as a library. Unlike the free
+/// functions, these are strict (missing data is an error) except where noted.
+impl Supervalue {
+    /// Borrow the value at `path`.
+    pub fn get_path(&self, path: &DataPath) -> Result<&Supervalue, Error> {
+        let mut at = self;
+        for (depth, seg) in path.0.iter().enumerate() {
+            match at {
+                Supervalue::Map(map) => {
+                    let key = map_key_segment(path, depth, seg)?;
+                    at = map.value.get(key).ok_or_else(|| Error::MissingKey {
+                        path: path.prefix(depth),
+                        key: key.to_string(),
+                    })?;
+                },
+                Supervalue::Vec(vec) => {
+                    let index = vec_index_segment(path, depth, seg)?;
+                    at = vec.value.get(index).ok_or_else(|| Error::OutOfBounds {
+                        path: path.prefix(depth),
+                        index: index,
+                    })?;
+                },
+                _ => return Err(Error::Untraversable { path: path.prefix(depth) }),
+            }
+        }
+        return Ok(at);
+    }
+
+    /// Mutably borrow the value at `path`.
+    pub fn get_path_mut(&mut self, path: &DataPath) -> Result<&mut Supervalue, Error> {
+        let mut at = self;
+        for (depth, seg) in path.0.iter().enumerate() {
+            match at {
+                Supervalue::Map(map) => {
+                    let key = map_key_segment(path, depth, seg)?;
+                    at = map.value.get_mut(key).ok_or_else(|| Error::MissingKey {
+                        path: path.prefix(depth),
+                        key: key.to_string(),
+                    })?;
+                },
+                Supervalue::Vec(vec) => {
+                    let index = vec_index_segment(path, depth, seg)?;
+                    at = vec.value.get_mut(index).ok_or_else(|| Error::OutOfBounds {
+                        path: path.prefix(depth),
+                        index: index,
+                    })?;
+                },
+                _ => return Err(Error::Untraversable { path: path.prefix(depth) }),
+            }
+        }
+        return Ok(at);
+    }
+
+    /// Replace or insert the value at `path`, creating missing parent maps.
+    pub fn set_path(&mut self, path: &DataPath, value: Supervalue) -> Result<&mut Self, Error> {
+        set(self, path, &value, true)?;
+        return Ok(self);
+    }
+
+    /// Remove the value at `path` from its parent.
+    pub fn delete_path(&mut self, path: &DataPath) -> Result<&mut Self, Error> {
+        delete(self, path, false)?;
+        return Ok(self);
+    }
+
+    /// Remove everything except the values at `paths`.
+    pub fn keep_paths(&mut self, paths: &[DataPath]) -> Result<&mut Self, Error> {
+        let mut out = None;
+        for path in paths {
+            keep(self, &mut out, path, false)?;
+        }
+        *self = out.unwrap_or(Supervalue::Null);
+        return Ok(self);
+    }
+
+    /// Wrap the values at `paths` in arrays if they aren't arrays already.
+    pub fn force_array_paths(&mut self, paths: &[DataPath]) -> &mut Self {
+        for path in paths {
+            force_array(self, path);
+        }
+        return self;
+    }
+
+    /// Convert strings that look like numbers or booleans to numbers and booleans.
+    pub fn infer_types(&mut self) -> &mut Self {
+        infer_types(self);
+        return self;
+    }
+
+    /// Convert strings in RFC 3339 or YAML timestamp format to datetimes.
+    pub fn infer_datetimes(&mut self) -> &mut Self {
+        infer_datetimes(self);
+        return self;
+    }
+
+    /// Convert to a single level map with joined path keys.
+    pub fn flatten(&mut self, options: &FlattenOptions) -> Result<&mut Self, Error> {
+        *self = flatten(std::mem::replace(self, Supervalue::Null), options)?;
+        return Ok(self);
+    }
+
+    /// Convert a single level map with joined path keys into nested data.
+    pub fn unflatten(&mut self, options: &FlattenOptions) -> Result<&mut Self, Error> {
+        *self = unflatten(std::mem::replace(self, Supervalue::Null), options)?;
+        return Ok(self);
+    }
+
+    /// Recursively merge `other` into this value.
+    pub fn merge_with(&mut self, other: Supervalue) -> &mut Self {
+        merge(self, other);
+        return self;
+    }
+
+    /// Remove everything not also present in `other`.
+    pub fn intersect_with(&mut self, other: &Supervalue) -> &mut Self {
+        intersect(self, other);
+        return self;
+    }
+
+    /// Remove everything also present in `other`.
+    pub fn subtract_with(&mut self, other: &Supervalue) -> Result<&mut Self, Error> {
+        subtract(self, other, false)?;
+        return Ok(self);
+    }
+
+    /// Replace all occurrences of `needle` with `data`. Errors if there were none.
+    pub fn search_set(&mut self, needle: &Supervalue, data: &Supervalue) -> Result<&mut Self, Error> {
+        if search_set(self, needle, data) == 0 {
+            return Err(Error::NeedleNotFound { needle: needle.clone() });
+        }
+        return Ok(self);
+    }
+
+    /// Delete all occurrences of `needle`. Errors if there were none.
+    pub fn search_delete(&mut self, needle: &Supervalue) -> Result<&mut Self, Error> {
+        if search_delete(self, needle) == 0 {
+            return Err(Error::NeedleNotFound { needle: needle.clone() });
+        }
+        return Ok(self);
+    }
+
+    /// Run the steps of `pipeline` on this value.
+    pub fn pipeline(self, pipeline: &Pipeline) -> Result<Supervalue, Error> {
+        return pipeline.run(self);
+    }
+}
+
+/// A step in a `Pipeline`, corresponding to a CLI command.
+#[derive(Debug, Clone)]
+pub enum PipelineStep {
+    Get {
+        path: DataPath,
+        missing_ok: bool,
+    },
+    Set {
+        path: DataPath,
+        value: Supervalue,
+        missing_ok: bool,
+    },
+    Delete {
+        paths: Vec<DataPath>,
+        missing_ok: bool,
+    },
+    Keep {
+        paths: Vec<DataPath>,
+        missing_ok: bool,
+    },
+    ForceArray {
+        paths: Vec<DataPath>,
+    },
+    InferTypes,
+    InferDatetimes,
+    Flatten {
+        options: FlattenOptions,
+    },
+    Unflatten {
+        options: FlattenOptions,
+    },
+    SearchSet {
+        needle: Supervalue,
+        data: Supervalue,
+        missing_ok: bool,
+    },
+    SearchDelete {
+        needle: Supervalue,
+        missing_ok: bool,
+    },
+    Intersect {
+        values: Vec<Supervalue>,
+    },
+    Subtract {
+        values: Vec<Supervalue>,
+        missing_ok: bool,
+    },
+    Merge {
+        values: Vec<Supervalue>,
+    },
+    With {
+        path: DataPath,
+        pipeline: Pipeline,
+        missing_ok: bool,
+    },
+}
+
+/// A reusable sequence of commands, built like
+/// `Pipeline::new().set(path, value).merge(other)`, equivalent to the command
+/// list on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    missing_ok: bool,
+    pub steps: Vec<PipelineStep>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        return Default::default();
+    }
+
+    /// Like the `--missing-ok` flag, applies to all steps added after this.
+    pub fn missing_ok(mut self, missing_ok: bool) -> Self {
+        self.missing_ok = missing_ok;
+        return self;
+    }
+
+    pub fn get(mut self, path: DataPath) -> Self {
+        self.steps.push(PipelineStep::Get {
+            path: path,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn set(mut self, path: DataPath, value: Supervalue) -> Self {
+        self.steps.push(PipelineStep::Set {
+            path: path,
+            value: value,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn delete(mut self, paths: Vec<DataPath>) -> Self {
+        self.steps.push(PipelineStep::Delete {
+            paths: paths,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn keep(mut self, paths: Vec<DataPath>) -> Self {
+        self.steps.push(PipelineStep::Keep {
+            paths: paths,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn force_array(mut self, paths: Vec<DataPath>) -> Self {
+        self.steps.push(PipelineStep::ForceArray { paths: paths });
+        return self;
+    }
+
+    pub fn infer_types(mut self) -> Self {
+        self.steps.push(PipelineStep::InferTypes);
+        return self;
+    }
+
+    pub fn infer_datetimes(mut self) -> Self {
+        self.steps.push(PipelineStep::InferDatetimes);
+        return self;
+    }
+
+    pub fn flatten(mut self, options: FlattenOptions) -> Self {
+        self.steps.push(PipelineStep::Flatten { options: options });
+        return self;
+    }
+
+    pub fn unflatten(mut self, options: FlattenOptions) -> Self {
+        self.steps.push(PipelineStep::Unflatten { options: options });
+        return self;
+    }
+
+    pub fn search_set(mut self, needle: Supervalue, data: Supervalue) -> Self {
+        self.steps.push(PipelineStep::SearchSet {
+            needle: needle,
+            data: data,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn search_delete(mut self, needle: Supervalue) -> Self {
+        self.steps.push(PipelineStep::SearchDelete {
+            needle: needle,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn intersect(mut self, values: Vec<Supervalue>) -> Self {
+        self.steps.push(PipelineStep::Intersect { values: values });
+        return self;
+    }
+
+    pub fn subtract(mut self, values: Vec<Supervalue>) -> Self {
+        self.steps.push(PipelineStep::Subtract {
+            values: values,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    pub fn merge(mut self, values: Vec<Supervalue>) -> Self {
+        self.steps.push(PipelineStep::Merge { values: values });
+        return self;
+    }
+
+    /// Run `pipeline` on the value at `path`, replacing it with the result.
+    pub fn with(mut self, path: DataPath, pipeline: Pipeline) -> Self {
+        self.steps.push(PipelineStep::With {
+            path: path,
+            pipeline: pipeline,
+            missing_ok: self.missing_ok,
+        });
+        return self;
+    }
+
+    /// Apply the steps in order to `at`, stopping at the first error.
+    pub fn run(&self, mut at: Supervalue) -> Result<Supervalue, Error> {
+        for step in &self.steps {
+            match step {
+                PipelineStep::Get { path, missing_ok } => {
+                    at = get(&mut at, path, *missing_ok)?.unwrap_or(Supervalue::Null);
+                },
+                PipelineStep::Set { path, value, missing_ok } => {
+                    set(&mut at, path, value, *missing_ok)?;
+                },
+                PipelineStep::Delete { paths, missing_ok } => {
+                    for path in paths {
+                        delete(&mut at, path, *missing_ok)?;
+                    }
+                },
+                PipelineStep::Keep { paths, missing_ok } => {
+                    let mut out = None;
+                    for path in paths {
+                        keep(&mut at, &mut out, path, *missing_ok)?;
+                    }
+                    at = out.unwrap_or(Supervalue::Null);
+                },
+                PipelineStep::ForceArray { paths } => {
+                    for path in paths {
+                        force_array(&mut at, path);
+                    }
+                },
+                PipelineStep::InferTypes => {
+                    infer_types(&mut at);
+                },
+                PipelineStep::InferDatetimes => {
+                    infer_datetimes(&mut at);
+                },
+                PipelineStep::Flatten { options } => {
+                    at = flatten(at, options)?;
+                },
+                PipelineStep::Unflatten { options } => {
+                    at = unflatten(at, options)?;
+                },
+                PipelineStep::SearchSet { needle, data, missing_ok } => {
+                    if search_set(&mut at, needle, data) == 0 && !*missing_ok {
+                        return Err(Error::NeedleNotFound { needle: needle.clone() });
+                    }
+                },
+                PipelineStep::SearchDelete { needle, missing_ok } => {
+                    if search_delete(&mut at, needle) == 0 && !*missing_ok {
+                        return Err(Error::NeedleNotFound { needle: needle.clone() });
+                    }
+                },
+                PipelineStep::Intersect { values } => {
+                    for other in values {
+                        intersect(&mut at, other);
+                    }
+                },
+                PipelineStep::Subtract { values, missing_ok } => {
+                    for other in values {
+                        subtract(&mut at, other, *missing_ok)?;
+                    }
+                },
+                PipelineStep::Merge { values } => {
+                    for other in values {
+                        merge(&mut at, other.clone());
+                    }
+                },
+                PipelineStep::With { path, pipeline, missing_ok } => {
+                    match at.get_path_mut(path) {
+                        Ok(sub) => {
+                            *sub = pipeline.run(std::mem::replace(sub, Supervalue::Null))?;
+                        },
+                        Err(_) if *missing_ok => { },
+                        Err(e) => return Err(e),
+                    }
+                },
+            }
+        }
+        return Ok(at);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::Pipeline,
+        crate::{
+            error::Error,
+            supervalue::Supervalue,
+            supervalue_path::DataPath,
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn methods() {
+        let mut source = Supervalue::from(json!({
+            "a": {
+                "b": [1, 2],
+            },
+        }));
+        source.set_path(&".a.c".parse().unwrap(), Supervalue::from(json!("x"))).unwrap().merge_with(Supervalue::from(json!({
+            "d": true,
+        })));
+        assert_eq!(*source.get_path(&".a.b.1".parse().unwrap()).unwrap(), Supervalue::from(json!(2)));
+        assert_eq!(source, Supervalue::from(json!({
+            "a": {
+                "b": [1, 2],
+                "c": "x",
+            },
+            "d": true,
+        })));
+    }
+
+    #[test]
+    fn typed_errors() {
+        let mut source = Supervalue::from(json!({
+            "a": {
+                "b": [1, 2],
+            },
+        }));
+        assert_eq!(source.get_path(&".a.x".parse().unwrap()), Err(Error::MissingKey {
+            path: DataPath(vec![json!("a")]),
+            key: "x".to_string(),
+        }));
+        assert_eq!(source.get_path(&".a.b.5".parse().unwrap()), Err(Error::OutOfBounds {
+            path: DataPath(vec![json!("a"), json!("b")]),
+            index: 5,
+        }));
+        assert_eq!(source.delete_path(&".a.b.0.c".parse().unwrap()).err(), Some(Error::Untraversable {
+            path: DataPath(vec![json!("a"), json!("b"), json!("0")]),
+        }));
+        assert_eq!(source.get_path(&".a.b.x".parse().unwrap()), Err(Error::TypeMismatch {
+            path: DataPath(vec![json!("a"), json!("b")]),
+            segment: json!("x"),
+        }));
+    }
+
+    #[test]
+    fn pipeline() {
+        let pipeline =
+            Pipeline::new()
+                .search_set(Supervalue::from(json!(1)), Supervalue::from(json!("__X")))
+                .missing_ok(true)
+                .set(".a.b".parse().unwrap(), Supervalue::from(json!("__X")))
+                .delete(vec![".missing".parse().unwrap()]);
+        assert_eq!(
+            Supervalue::from(json!({
+                "c": 1
+            })).pipeline(&pipeline).unwrap(),
+            Supervalue::from(json!({
+                "a": {
+                    "b": "__X"
+                },
+                "c": "__X"
+            }))
+        );
+        let pipeline =
+            Pipeline::new()
+                .with(".a".parse().unwrap(), Pipeline::new().merge(vec![Supervalue::from(json!({
+                    "c": 2
+                }))]))
+                .missing_ok(true)
+                .with(".x".parse().unwrap(), Pipeline::new().infer_types());
+        assert_eq!(
+            Supervalue::from(json!({
+                "a": {
+                    "b": 1
+                },
+                "c": 1
+            })).pipeline(&pipeline).unwrap(),
+            Supervalue::from(json!({
+                "a": {
+                    "b": 1,
+                    "c": 2
+                },
+                "c": 1
+            }))
+        );
+        assert_eq!(
+            Supervalue::from(json!(1)).pipeline(&Pipeline::new().search_delete(Supervalue::from(json!(2)))).err(),
+            Some(Error::NeedleNotFound { needle: Supervalue::from(json!(2)) })
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use {
+    std::{
+        fs::{
+            self,
+            OpenOptions,
+        },
+        io::Write,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// What to do when the file to write is a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symlinks {
+    /// Write to the file the link points to, keeping the link
+    #[default]
+    Follow,
+    /// Fail without writing
+    Refuse,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AtomicWriteOptions {
+    pub symlinks: Symlinks,
+    /// If set and the file already exists, keep a copy of the original file at the
+    /// path with this suffix appended.
+    pub backup_suffix: Option<String>,
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    return path.with_file_name(format!(".{}.{}.hoj-tmp", name, std::process::id()));
+}
+
+enum TempError {
+    /// The temporary file can't be given the original file's owner and group
+    Ownership,
+    Other(String),
+}
+
+fn write_temp(temp: &Path, path: &Path, data: &[u8]) -> Result<(), TempError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    // Keep the data private until the final permissions are applied
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+    let mut file =
+        options
+            .open(temp)
+            .map_err(|e| TempError::Other(format!("Error creating temporary file {:?}: {}", temp, e)))?;
+    if let Ok(meta) = fs::metadata(path) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            std::os::unix::fs::fchown(&file, Some(meta.uid()), Some(meta.gid())).map_err(|_| TempError::Ownership)?;
+        }
+        file
+            .set_permissions(meta.permissions())
+            .map_err(
+                |e| TempError::Other(format!("Error setting permissions of temporary file {:?}: {}", temp, e)),
+            )?;
+    }
+    file
+        .write_all(data)
+        .map_err(|e| TempError::Other(format!("Error writing temporary file {:?}: {}", temp, e)))?;
+    file.sync_all().map_err(|e| TempError::Other(format!("Error writing temporary file {:?}: {}", temp, e)))?;
+    return Ok(());
+}
+
+/// Overwrite the file in place, keeping its inode (and so owner, group and
+/// permissions). Not atomic.
+fn write_in_place(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut file =
+        OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| format!("Error opening {:?} for writing: {}", path, e))?;
+    file.write_all(data).map_err(|e| format!("Error writing {:?}: {}", path, e))?;
+    file.sync_all().map_err(|e| format!("Error writing {:?}: {}", path, e))?;
+    return Ok(());
+}
+
+/// Replace the contents of the file at `path` atomically: the data is written to
+/// a temporary file in the same directory, which is given the original file's
+/// permissions (and owner and group on unix), then renamed over the original. If
+/// anything fails the original is left untouched.
+///
+/// If the owner and group can't be kept (like when editing a group-writable file
+/// owned by another user) the file is instead overwritten in place, which isn't
+/// atomic.
+pub fn write_atomic(path: &Path, data: &[u8], options: &AtomicWriteOptions) -> Result<(), String> {
+    let mut path = path.to_path_buf();
+    if fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink()) {
+        match options.symlinks {
+            Symlinks::Follow => {
+                path = fs::canonicalize(&path).map_err(|e| format!("Error resolving symlink {:?}: {}", path, e))?;
+            },
+            Symlinks::Refuse => {
+                return Err(format!("Refusing to write to {:?} since it's a symlink", path));
+            },
+        }
+    }
+    if let (Some(suffix), true) = (&options.backup_suffix, path.exists()) {
+        let mut backup = path.clone().into_os_string();
+        backup.push(suffix);
+        fs::copy(&path, &backup).map_err(|e| format!("Error backing up {:?} to {:?}: {}", path, backup, e))?;
+    }
+    let temp = temp_path(&path);
+    match write_temp(&temp, &path, data) {
+        Ok(_) => { },
+        Err(TempError::Ownership) => {
+            _ = fs::remove_file(&temp);
+            return write_in_place(&path, data);
+        },
+        Err(TempError::Other(e)) => {
+            _ = fs::remove_file(&temp);
+            return Err(e);
+        },
+    }
+    if let Err(e) = fs::rename(&temp, &path) {
+        _ = fs::remove_file(&temp);
+        return Err(format!("Error replacing {:?} with temporary file {:?}: {}", path, temp, e));
+    }
+
+    // Make the rename durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if let Ok(dir) = fs::File::open(dir) {
+            _ = dir.sync_all();
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            write_atomic,
+            AtomicWriteOptions,
+            Symlinks,
+        },
+        std::fs,
+    };
+
+    #[test]
+    fn write() {
+        let dir = std::env::temp_dir().join(format!("hoj-atomic-write-{}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.json");
+        fs::write(&path, "old").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        let link = dir.join("link.json");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("a.json", &link).unwrap();
+        #[cfg(not(unix))]
+        fs::copy(&path, &link).unwrap();
+        write_atomic(&link, b"new", &AtomicWriteOptions {
+            symlinks: Symlinks::Follow,
+            backup_suffix: Some(".bak".to_string()),
+        }).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dir.join("a.json.bak")).unwrap(), "old");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+            assert!(write_atomic(&link, b"newer", &AtomicWriteOptions {
+                symlinks: Symlinks::Refuse,
+                backup_suffix: None,
+            }).is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,32 +1,87 @@
 use {
     aargvark::{
+        traits_impls::AargvarkFromStr,
         Aargvark,
+        VarkRet,
         vark,
+        vark_explicit,
     },
     flowcontrol::superif,
     hammer_of_json::{
+        atomic_write::{
+            write_atomic,
+            AtomicWriteOptions,
+            Symlinks as LibSymlinks,
+        },
+        binary::{
+            to_cbor_vec,
+            to_msgpack_vec,
+        },
+        check::diff_bytes,
+        codegen::{
+            codegen_schema,
+            codegen_value,
+        },
         delete::delete,
+        env_file::to_env_string,
+        error::Error,
+        flatten::{
+            flatten,
+            unflatten,
+            ArrayStyle,
+            FlattenOptions,
+        },
         get::get,
+        infer_types::{
+            infer_datetimes,
+            infer_types,
+        },
+        ini_file::to_ini_string,
         intersect::intersect,
+        force_array::force_array,
+        json5_writer::to_json5_string_pretty,
         keep::keep,
         merge::merge,
+        properties::to_properties_string,
         search_delete::search_delete,
         search_set::search_set,
         set::set,
         subtract::subtract,
         supervalue::{
+            file_source_path,
             AargSupervalue,
             AargSupervalueOriginalFormat,
+            ConvertOptions,
             Supervalue,
+            SupervalueMap,
+            TomlNull as LibTomlNull,
         },
         supervalue_path::DataPath,
+        table::{
+            parse_csv_columns,
+            to_csv_string,
+        },
+        xml::to_xml_string,
+        yaml_anchors::{
+            resolve_merge_keys,
+            to_yaml_string_with_anchors,
+        },
+        yaml_tags::{
+            tags_from_keys,
+            tags_to_style,
+            TagStyle as LibTagStyle,
+        },
     },
     jsonschema::Validator,
     serde_json::json,
     std::{
+        collections::HashMap,
         env::current_dir,
-        fs::write,
-        path::PathBuf,
+        io::Write,
+        path::{
+            Path,
+            PathBuf,
+        },
         process::exit,
     },
 };
@@ -50,7 +105,37 @@ struct SetCommand {
     /// Where to place the data in `source`
     path: DataPath,
     /// Data to set in `source`
-    data: AargSupervalue,
+    data: ValueArg,
+}
+
+#[derive(Aargvark)]
+struct ForceArrayCommand {
+    /// Paths of values to wrap in an array if they aren't already arrays
+    paths: Vec<DataPath>,
+}
+
+#[derive(Aargvark)]
+struct FlattenCommand {
+    /// String to join path segments with, defaults to `.`
+    #[vark(flag = "--separator", flag = "-s")]
+    separator: Option<String>,
+    /// Write array indices in brackets appended to the previous segment, like
+    /// `a[0].b`, rather than as normal segments like `a.0.b`
+    #[vark(flag = "--brackets", flag = "-b")]
+    brackets: Option<()>,
+}
+
+impl FlattenCommand {
+    fn options(&self) -> FlattenOptions {
+        return FlattenOptions {
+            separator: self.separator.clone().unwrap_or_else(|| ".".to_string()),
+            array_style: if self.brackets.is_some() {
+                ArrayStyle::Brackets
+            } else {
+                ArrayStyle::Separator
+            },
+        };
+    }
 }
 
 #[derive(Aargvark)]
@@ -76,9 +161,9 @@ struct KeepCommand {
 #[derive(Aargvark)]
 struct SearchSetCommand {
     /// Data to find in `source`
-    needle: AargSupervalue,
+    needle: ValueArg,
     /// Data to replace `needle`
-    data: AargSupervalue,
+    data: ValueArg,
     /// Even if the needle isn't found don't exit with an error.
     #[vark(flag = "--missing-ok", flag = "-m")]
     missing_ok: Option<()>,
@@ -87,7 +172,7 @@ struct SearchSetCommand {
 #[derive(Aargvark)]
 struct SearchDeleteCommand {
     /// Data to delete from `source`
-    needle: AargSupervalue,
+    needle: ValueArg,
     /// Even if the needle isn't found don't exit with an error.
     #[vark(flag = "--missing-ok", flag = "-m")]
     missing_ok: Option<()>,
@@ -96,7 +181,7 @@ struct SearchDeleteCommand {
 #[derive(Aargvark)]
 struct IntersectCommand {
     /// Data to intersect with `source`
-    values: Vec<AargSupervalue>,
+    values: Vec<ValueArg>,
 }
 
 #[derive(Aargvark)]
@@ -106,20 +191,132 @@ struct SubtractCommand {
     #[vark(flag = "--missing-ok", flag = "-m")]
     missing_ok: Option<()>,
     /// Data to subtract from `source`
-    values: Vec<AargSupervalue>,
+    values: Vec<ValueArg>,
 }
 
 #[derive(Aargvark)]
 struct MergeCommand {
     /// Data to merge into `source`
-    values: Vec<AargSupervalue>,
+    values: Vec<ValueArg>,
 }
 
 #[derive(Aargvark)]
 struct ValidateJsonSchemaCommand {
     /// External schema to validate `source` against. Overrides `$schema` in `source`
     /// if present.
-    external: Option<AargSupervalue>,
+    external: Option<ValueArg>,
+}
+
+/// The `end` keyword closing `with`.
+#[derive(Aargvark)]
+enum End {
+    End,
+}
+
+#[derive(Aargvark)]
+struct WithCommand {
+    /// If the path is missing, skip the commands rather than aborting.
+    #[vark(flag = "--missing-ok", flag = "-m")]
+    missing_ok: Option<()>,
+    /// Path of the subtree to run the commands on
+    path: DataPath,
+    /// Commands to run on the subtree, followed by `end`
+    commands: Vec<Command>,
+    #[allow(dead_code)]
+    end: End,
+}
+
+#[derive(Aargvark)]
+struct ExistsPredicate {
+    /// Path that must exist
+    path: DataPath,
+}
+
+#[derive(Aargvark)]
+struct EqualsPredicate {
+    /// Path of the value to compare
+    path: DataPath,
+    /// Value it must equal
+    value: ValueArg,
+}
+
+#[derive(Aargvark)]
+struct SchemaValidPredicate {
+    /// External schema, like `validate-json-schema`
+    external: Option<ValueArg>,
+}
+
+#[derive(Aargvark)]
+enum Predicate {
+    /// The path exists in the data
+    Exists(ExistsPredicate),
+    /// The value at the path equals a value (false if it's missing)
+    Equals(EqualsPredicate),
+    /// The data is valid against its schema, as with `validate-json-schema`
+    SchemaValid(SchemaValidPredicate),
+}
+
+#[derive(Aargvark)]
+struct ElseBlock {
+    /// Commands to run if the condition is false, followed by `end`
+    commands: Vec<Command>,
+    #[allow(dead_code)]
+    end: End,
+}
+
+#[derive(Aargvark)]
+enum IfEnd {
+    Else(ElseBlock),
+    End,
+}
+
+#[derive(Aargvark)]
+struct IfBody {
+    /// Commands to run if the condition is true, followed by `else` or `end`
+    commands: Vec<Command>,
+    end: IfEnd,
+}
+
+#[derive(Aargvark)]
+struct IfExistsCommand {
+    predicate: ExistsPredicate,
+    body: IfBody,
+}
+
+#[derive(Aargvark)]
+struct IfEqualsCommand {
+    predicate: EqualsPredicate,
+    body: IfBody,
+}
+
+#[derive(Aargvark)]
+struct IfSchemaValidCommand {
+    predicate: SchemaValidPredicate,
+    body: IfBody,
+}
+
+#[derive(Aargvark)]
+struct AssertCommand {
+    /// Error message if the predicate doesn't hold
+    #[vark(flag = "--message")]
+    message: Option<String>,
+    predicate: Predicate,
+}
+
+#[derive(Aargvark)]
+struct VariableCommand {
+    /// Name of the variable
+    name: String,
+}
+
+#[derive(Aargvark)]
+struct CodegenCommand {
+    /// Treat `source` as a JSON Schema rather than inferring types from the data.
+    #[vark(flag = "--schema", flag = "-s")]
+    schema: Option<()>,
+    /// Name of the root type, defaults to `Root`.
+    #[vark(flag = "--name", flag = "-n")]
+    name: Option<String>,
 }
 
 #[derive(Aargvark)]
@@ -134,6 +331,24 @@ enum Command {
     Delete(DeleteCommand),
     /// Remove everything but the subtrees at paths.
     Keep(KeepCommand),
+    /// Wrap the values at paths in single element arrays if they aren't arrays
+    /// already, for stable output from XML where repeated elements only become
+    /// arrays when there's more than one. Arrays along a path that aren't indexed
+    /// are applied to element-wise, and missing values are ignored.
+    ForceArray(ForceArrayCommand),
+    /// Convert strings that look like numbers or booleans into numbers and
+    /// booleans, for data from formats without types like CSV or XML.
+    InferTypes,
+    /// Convert strings in RFC 3339 or YAML timestamp format (like `2001-12-14` or
+    /// `2001-12-14 21:59:43.10 -5`) into datetimes, which are written as native
+    /// datetimes in TOML and compare by value (equal instants are equal).
+    InferDatetimes,
+    /// Convert the data into a single level map, with keys made by joining the path
+    /// segments to each primitive value.
+    Flatten(FlattenCommand),
+    /// Convert a single level map with joined path keys into nested data. Numeric
+    /// segments (or bracketed indices with `--brackets`) become arrays.
+    Unflatten(FlattenCommand),
     /// Search for matching values and replace them with a new value. If the value is
     /// found in a map, the key that holds it will be removed with the value. If it's
     /// found in an array, the array element will be removed shifting later elements
@@ -149,19 +364,197 @@ enum Command {
     /// Add the data in each file, sequentually. Objects fields are recursed, while all
     /// other values are replaced atomically.
     Merge(MergeCommand),
-    /// Validate a file against a schema, either internal (via a root `"$schema"` key)
-    /// or external. Doesn't change the input, but exits with an error if validation
-    /// fails.
+    /// Validate a file against a schema, either internal (via a root `"$schema"` key,
+    /// or for YAML and TOML sources a `# yaml-language-server: $schema=...` or
+    /// `#:schema ...` comment) or external. Doesn't change the input, but exits with
+    /// an error if validation fails.
     ValidateJsonSchema(ValidateJsonSchemaCommand),
+    /// Run commands (up to a matching `end`) on the subtree at a path and replace
+    /// the subtree with the result, like `with .spec.template merge f:x.json end`.
+    With(WithCommand),
+    /// Run commands (up to `else` or `end`) if the path exists, otherwise run the
+    /// commands between `else` and `end` if present.
+    IfExists(IfExistsCommand),
+    /// Like `if-exists`, if the value at the path equals a value.
+    IfEquals(IfEqualsCommand),
+    /// Like `if-exists`, if the data is valid against its schema (or an external
+    /// schema).
+    IfSchemaValid(IfSchemaValidCommand),
+    /// Exit with a validation error (with a message from `--message`) if the
+    /// predicate doesn't hold.
+    Assert(AssertCommand),
+    /// Save the current data as a variable, for use as a `v:NAME` value in later
+    /// commands. The data is unchanged.
+    Store(VariableCommand),
+    /// Replace the current data with a variable saved by `store`.
+    Load(VariableCommand),
+    /// Replace the data with a string of Rust type definitions (with `serde`
+    /// attributes) matching it, or matching the schema if it's a JSON Schema. Use
+    /// with `-u` to output the code directly.
+    Codegen(CodegenCommand),
+}
+
+#[derive(Aargvark, Default, Clone, PartialEq, Eq)]
+enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Aargvark, Default, Clone, Copy, PartialEq, Eq)]
+enum TagStyle {
+    /// `{"tag": "!Ref", "value": "foo"}`
+    #[default]
+    Map,
+    /// `{"!Ref": "foo"}`, like CloudFormation
+    Key,
+    /// `"foo"`, dropping the tag
+    Value,
+}
+
+#[derive(Aargvark, Default, Clone, Copy, PartialEq, Eq)]
+enum TomlNull {
+    /// Leave out map entries and array elements that are null
+    #[default]
+    Omit,
+    /// Exit with an error
+    Error,
+    /// Write an empty string
+    Empty,
+}
+
+#[derive(Aargvark, Default, Clone, Copy, PartialEq, Eq)]
+enum Symlinks {
+    /// Write to the file the link points to, keeping the link
+    #[default]
+    Follow,
+    /// Exit with an error
+    Refuse,
 }
 
-#[derive(Aargvark, Default)]
+#[derive(Aargvark, Default, Clone, Copy, PartialEq, Eq)]
 enum Format {
     CompactJson,
     #[default]
     PrettyJson,
+    #[vark(name = "json5")]
+    Json5,
     Toml,
     Yaml,
+    Ron,
+    Xml,
+    /// An array of objects as comma separated values with a header row
+    Csv,
+    /// An array of objects as tab separated values with a header row
+    Tsv,
+    /// A map of primitives as a `.env` file
+    Env,
+    /// A map as an INI file, with nested maps of primitives as sections
+    Ini,
+    /// Flattened as a Java `.properties` file, like `a.b[0]=value`
+    Properties,
+    /// Binary CBOR
+    Cbor,
+    /// Binary MessagePack
+    Msgpack,
+}
+
+/// A command argument value: data, or `v:NAME` for a value saved with `store`
+/// earlier in the pipeline.
+enum ValueArg {
+    Value(AargSupervalue),
+    Variable(String),
+}
+
+impl AargvarkFromStr for ValueArg {
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let Some(name) = s.strip_prefix("v:") {
+            return Ok(ValueArg::Variable(name.to_string()));
+        }
+        return Ok(ValueArg::Value(AargSupervalue::from_str(s)?));
+    }
+
+    fn build_help_pattern(state: &mut aargvark::help::HelpState) -> aargvark::help::HelpPattern {
+        return AargSupervalue::build_help_pattern(state);
+    }
+}
+
+fn get_variable<'a>(variables: &'a HashMap<String, Supervalue>, name: &str) -> Result<&'a Supervalue, CliError> {
+    return variables
+        .get(name)
+        .ok_or_else(
+            || CliError::parse(
+                format!("Variable [{}] hasn't been set with `store` yet", name),
+            ).detail("variable", json!(name)),
+        );
+}
+
+impl ValueArg {
+    fn get<'a>(&'a self, variables: &'a HashMap<String, Supervalue>) -> Result<&'a Supervalue, CliError> {
+        match self {
+            ValueArg::Value(v) => return Ok(&v.value),
+            ValueArg::Variable(name) => return get_variable(variables, name),
+        }
+    }
+}
+
+/// A source value, or if it's a file path pattern (like `*.json` or
+/// `fy:manifests/**/*.yaml`) every matching file. Files that can't be read, and
+/// errors parsing matching files, are kept to report with the other results.
+struct SourceArg(Vec<(String, Result<AargSupervalue, CliError>)>);
+
+/// Parse a source, with an `Io` error if it refers to a file that can't be read.
+fn read_source(s: &str) -> Result<AargSupervalue, CliError> {
+    if let Some(path) = file_source_path(s).filter(|p| *p != "-") {
+        if let Err(e) = std::fs::File::open(path) {
+            return Err(CliError::io(format!("Error reading [{}]: {}", path, e)));
+        }
+    }
+    return AargSupervalue::from_str(s).map_err(CliError::parse);
+}
+
+impl AargvarkFromStr for SourceArg {
+    fn from_str(s: &str) -> Result<Self, String> {
+        let e = match read_source(s) {
+            Ok(v) => return Ok(SourceArg(vec![(s.to_string(), Ok(v))])),
+            Err(e) => e,
+        };
+        // Only file sources can be patterns. Unprefixed arguments are inline JSON unless they name files, so
+        // report the JSON error if they don't match any.
+        let (prefix, pattern) = match file_source_path(s) {
+            Some(path) => (&s[.. s.len() - path.len()], path),
+            None => ("", s),
+        };
+        if !pattern.contains(['*', '?', '[']) {
+            if e.category == ErrorCategory::Io {
+                return Ok(SourceArg(vec![(s.to_string(), Err(e))]));
+            }
+            return Err(e.message);
+        }
+        let paths = match glob::glob(pattern) {
+            Ok(paths) => paths,
+            Err(_) if prefix.is_empty() => return Err(e.message),
+            Err(e) => return Err(format!("Invalid file pattern [{}]: {}", pattern, e)),
+        };
+        let mut out = vec![];
+        for path in paths {
+            let path = path.map_err(|e| format!("Error listing files for pattern [{}]: {}", pattern, e))?;
+            let path = path.to_string_lossy().to_string();
+            let value = read_source(&format!("{}{}", prefix, path));
+            out.push((path, value));
+        }
+        if out.is_empty() {
+            if prefix.is_empty() {
+                return Err(e.message);
+            }
+            return Err(format!("File pattern [{}] didn't match any files", pattern));
+        }
+        return Ok(SourceArg(out));
+    }
+
+    fn build_help_pattern(state: &mut aargvark::help::HelpState) -> aargvark::help::HelpPattern {
+        return AargSupervalue::build_help_pattern(state);
+    }
 }
 
 /// This is a collection of tools for common json (and yaml, and toml) document
@@ -172,264 +565,1634 @@ struct Args {
     #[vark(flag = "--format", flag = "-f")]
     format: Option<Format>,
     /// Source JSON file
-    source: AargSupervalue,
+    source: SourceArg,
+    /// More sources to process the same way, each output separately (or written
+    /// back with `-i`)
+    more_sources: Vec<SourceArg>,
     /// Modify source in-place
     #[vark(flag = "--in-place", flag = "-i")]
     in_place: Option<()>,
+    /// Don't write anything, instead compare the result with the source file and
+    /// print a unified diff of what `-i` would write. Exits with an error if they
+    /// differ.
+    #[vark(flag = "--check")]
+    check: Option<()>,
+    /// Write the result to this file (atomically) instead of stdout. Relative
+    /// paths are relative to the source file's directory. The format is
+    /// determined by the extension if `-f` isn't specified.
+    #[vark(flag = "--output", flag = "-o")]
+    output: Option<PathBuf>,
+    /// With `-i` or `-o`, what to do if the file is a symlink, defaults to `follow`.
+    #[vark(flag = "--symlinks")]
+    symlinks: Option<Symlinks>,
+    /// With `-i` or `-o`, keep a copy of the original file with this suffix appended
+    /// (like `.bak`).
+    #[vark(flag = "--backup-suffix")]
+    backup_suffix: Option<String>,
     /// If the result is a string value, output as an unquoted (non-json) string
     #[vark(flag = "--unquote", flag = "-u")]
     unquote: Option<()>,
+    /// How to write errors to stderr, defaults to `text`. `json` writes a single
+    /// object with the error `category`, `exit_code`, `message` and other
+    /// category-specific details.
+    #[vark(flag = "--error-format")]
+    error_format: Option<ErrorFormat>,
+    /// For `csv` and `tsv` output, a comma separated list of the columns to write
+    /// (nested values with dotted paths, like `a.b`). Quote names containing commas
+    /// like in CSV (`"a,b",c`). Defaults to all columns in order of first
+    /// appearance.
+    #[vark(flag = "--csv-columns")]
+    csv_columns: Option<String>,
+    /// When the output format can't represent a number exactly (like integers
+    /// larger than 64 bits in YAML, or `NaN` in JSON), convert it to the nearest
+    /// representable value (or `null`) instead of failing.
+    #[vark(flag = "--lossy-numbers")]
+    lossy_numbers: Option<()>,
+    /// How to write YAML tags (like `!Ref foo`) in output formats other than YAML,
+    /// defaults to `map`. With `key`, single key maps with a key starting with `!`
+    /// are also turned into tags when writing YAML.
+    #[vark(flag = "--tag-style")]
+    tag_style: Option<TagStyle>,
+    /// How to write nulls in TOML output, which doesn't have nulls, defaults to
+    /// `omit`.
+    #[vark(flag = "--toml-null")]
+    toml_null: Option<TomlNull>,
+    /// Resolve YAML 1.1 merge keys (`<<: *base`) in a YAML source, adding the
+    /// merged entries to the map rather than keeping a literal `<<` key.
+    #[vark(flag = "--yaml-merge-keys")]
+    yaml_merge_keys: Option<()>,
+    /// When writing YAML, restore anchors and aliases from a YAML source where the
+    /// anchored value and the aliases are unchanged, rather than writing every
+    /// alias in full.
+    #[vark(flag = "--yaml-anchors")]
+    yaml_anchors: Option<()>,
+    /// Read commands from a JSON or YAML file, run before any commands on the
+    /// command line.
+    #[vark(flag = "--script")]
+    script: Option<PathBuf>,
     commands: Vec<Command>,
 }
 
-fn main1() -> Result<(), String> {
-    let root_args = vark::<Args>();
-    let mut at = root_args.source.value;
-    for command in root_args.commands {
+/// Error categories, each with a distinct exit code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ErrorCategory {
+    /// Arguments, values, or schemas couldn't be parsed or are invalid
+    Parse,
+    /// A path in a command couldn't be resolved in the data
+    Path,
+    /// A `search-*` command's needle wasn't found
+    NeedleNotFound,
+    /// The data didn't pass JSON Schema validation or an `assert`
+    Validation,
+    /// Reading or writing files or network resources failed
+    Io,
+    /// The data can't be represented in the output format (or flattened)
+    Conversion,
+    /// With `--check`, the result differs from the source
+    Changed,
+}
+
+impl ErrorCategory {
+    fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCategory::Parse => return 2,
+            ErrorCategory::Path => return 3,
+            ErrorCategory::NeedleNotFound => return 4,
+            ErrorCategory::Validation => return 5,
+            ErrorCategory::Io => return 6,
+            ErrorCategory::Conversion => return 7,
+            ErrorCategory::Changed => return 8,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::Parse => return "parse",
+            ErrorCategory::Path => return "path",
+            ErrorCategory::NeedleNotFound => return "needle_not_found",
+            ErrorCategory::Validation => return "validation",
+            ErrorCategory::Io => return "io",
+            ErrorCategory::Conversion => return "conversion",
+            ErrorCategory::Changed => return "changed",
+        }
+    }
+}
+
+struct CliError {
+    category: ErrorCategory,
+    message: String,
+    /// Additional fields for the JSON error format
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CliError {
+    fn new(category: ErrorCategory, message: impl Into<String>) -> Self {
+        return CliError {
+            category: category,
+            message: message.into(),
+            details: Default::default(),
+        };
+    }
+
+    fn parse(message: impl Into<String>) -> Self {
+        return CliError::new(ErrorCategory::Parse, message);
+    }
+
+    fn io(message: impl Into<String>) -> Self {
+        return CliError::new(ErrorCategory::Io, message);
+    }
+
+    fn conversion(message: impl Into<String>) -> Self {
+        return CliError::new(ErrorCategory::Conversion, message);
+    }
+
+    fn detail(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.details.insert(key.to_string(), value);
+        return self;
+    }
+
+    fn context(mut self, context: impl std::fmt::Display) -> Self {
+        self.message = format!("{}:\n{}", context, self.message);
+        return self;
+    }
+}
+
+fn error_details(e: &Error) -> serde_json::Map<String, serde_json::Value> {
+    let mut out = serde_json::Map::new();
+    match e {
+        Error::MissingKey { path, key } => {
+            out.insert("path".to_string(), json!(path.0));
+            out.insert("key".to_string(), json!(key));
+        },
+        Error::OutOfBounds { path, index } => {
+            out.insert("path".to_string(), json!(path.0));
+            out.insert("index".to_string(), json!(index));
+        },
+        Error::Untraversable { path } => {
+            out.insert("path".to_string(), json!(path.0));
+        },
+        Error::TypeMismatch { path, segment } => {
+            out.insert("path".to_string(), json!(path.0));
+            out.insert("segment".to_string(), segment.clone());
+        },
+        Error::NeedleNotFound { needle } => {
+            out.insert("needle".to_string(), needle.clone().into());
+        },
+        Error::FlattenConflict { key } => {
+            out.insert("key".to_string(), json!(key));
+        },
+        Error::Multiple(errors) => {
+            out.insert("errors".to_string(), serde_json::Value::Array(errors.iter().map(|e| {
+                let mut details = error_details(e);
+                details.insert("message".to_string(), json!(e.to_string()));
+                return serde_json::Value::Object(details);
+            }).collect()));
+        },
+    }
+    return out;
+}
+
+fn error_category(e: &Error) -> ErrorCategory {
+    match e {
+        Error::MissingKey { .. } | Error::OutOfBounds { .. } | Error::Untraversable { .. } | Error::TypeMismatch { .. } => {
+            return ErrorCategory::Path;
+        },
+        Error::NeedleNotFound { .. } => return ErrorCategory::NeedleNotFound,
+        Error::FlattenConflict { .. } => return ErrorCategory::Conversion,
+        Error::Multiple(errors) => return errors.first().map(error_category).unwrap_or(ErrorCategory::Path),
+    }
+}
+
+impl From<Error> for CliError {
+    fn from(e: Error) -> Self {
+        return CliError {
+            category: error_category(&e),
+            message: e.to_string(),
+            details: error_details(&e),
+        };
+    }
+}
+
+/// Parse arguments, reporting argument errors as `Parse` errors rather than
+/// exiting.
+fn parse_args() -> Result<Args, CliError> {
+    if std::env::var_os("AARGVARK_COMPLETE").is_some() {
+        return Ok(vark::<Args>());
+    }
+    let mut args = std::env::args();
+    let command = args.next();
+    match vark_explicit::<Args>(command, args.collect()) {
+        Ok(VarkRet::Ok(args)) => return Ok(args),
+        Ok(VarkRet::Help(help)) => {
+            println!("{}", help.render());
+            exit(0);
+        },
+        Err(e) => return Err(CliError::parse(e.to_string())),
+    }
+}
+
+/// Load a schema from a url or path. For comment directives, pass the source's
+/// directory as `directive_directory` to resolve relative paths (and `file://`
+/// urls) against it; otherwise paths are read as is, relative to the working
+/// directory.
+fn load_schema(addr: &str, directive_directory: Option<&Path>) -> Result<serde_json::Value, CliError> {
+    if addr.starts_with("https://") || addr.starts_with("http://") {
+        return Ok(
+            ureq::get(addr)
+                .call()
+                .map_err(|e| CliError::io(format!("Error sending request for external schema at [{}]: {}", addr, e)))?
+                .body_mut()
+                .read_json()
+                .map_err(
+                    |e| CliError::parse(
+                        format!("Error reading JSON from external schema response at [{}]: {}", addr, e),
+                    ),
+                )?,
+        );
+    } else {
+        let path = match directive_directory {
+            Some(dir) => dir.join(addr.strip_prefix("file://").unwrap_or(addr)),
+            None => PathBuf::from(addr),
+        };
+        let schema =
+            std::fs::read(
+                &path,
+            ).map_err(|e| CliError::io(format!("Error loading schema [{}] from disk: {}", addr, e)))?;
+        return Ok(
+            serde_json::from_slice::<serde_json::Value>(
+                &schema,
+            ).map_err(|e| CliError::parse(format!("Schema at [{}] is invalid JSON: {}", addr, e)))?,
+        );
+    }
+}
+
+/// The directory to resolve relative paths against: the source file's directory,
+/// or the working directory if the source isn't a file.
+fn source_directory(source: &aargvark::traits_impls::Source) -> Result<PathBuf, CliError> {
+    match source {
+        aargvark::traits_impls::Source::Stdin => return current_dir().map_err(
+            |e| CliError::io(
+                format!(
+                    "No file source to root relative paths and couldn't determine working directory from executable working directory: {}",
+                    e
+                ),
+            ),
+        ),
+        aargvark::traits_impls::Source::File(v) => return Ok(
+            v
+                .canonicalize()
+                .map_err(
+                    |e| CliError::io(
+                        format!("Error determining absolute path of source [{}]: {}", v.to_string_lossy(), e),
+                    ),
+                )?
+                .parent()
+                .ok_or_else(
+                    || CliError::io(
+                        format!("Could not determine parent directory of source file [{}]", v.to_string_lossy()),
+                    ),
+                )?
+                .to_path_buf(),
+        ),
+    }
+}
+
+/// The default output format for data read in `format`.
+fn default_format(format: AargSupervalueOriginalFormat) -> Format {
+    match format {
+        AargSupervalueOriginalFormat::Json => return Format::PrettyJson,
+        AargSupervalueOriginalFormat::Json5 => return Format::Json5,
+        AargSupervalueOriginalFormat::Yaml => return Format::Yaml,
+        AargSupervalueOriginalFormat::Toml => return Format::Toml,
+        AargSupervalueOriginalFormat::Ron => return Format::Ron,
+        AargSupervalueOriginalFormat::Xml => return Format::Xml,
+        AargSupervalueOriginalFormat::Csv => return Format::Csv,
+        AargSupervalueOriginalFormat::Tsv => return Format::Tsv,
+        AargSupervalueOriginalFormat::Env => return Format::Env,
+        AargSupervalueOriginalFormat::Ini => return Format::Ini,
+        AargSupervalueOriginalFormat::Properties => return Format::Properties,
+        AargSupervalueOriginalFormat::Cbor => return Format::Cbor,
+        AargSupervalueOriginalFormat::Msgpack => return Format::Msgpack,
+    }
+}
+
+/// The format to write to `path`, from its name.
+fn output_format(path: &Path) -> Option<AargSupervalueOriginalFormat> {
+    let name = path.file_name()?.to_str()?;
+    if name == ".env" || name.starts_with(".env.") {
+        return Some(AargSupervalueOriginalFormat::Env);
+    }
+    return path.extension()?.to_str().and_then(AargSupervalueOriginalFormat::from_extension);
+}
+
+/// Arguments of a command in a script, removed as they're read.
+struct ScriptArgs<'a> {
+    command: String,
+    directory: &'a Path,
+    args: HashMap<String, Supervalue>,
+}
+
+impl<'a> ScriptArgs<'a> {
+    /// Take an argument without resolving `$file` and `$env` references.
+    fn take_raw(&mut self, key: &str) -> Option<Supervalue> {
+        return self.args.remove(key);
+    }
+
+    fn take(&mut self, key: &str) -> Result<Option<Supervalue>, String> {
+        return self.take_raw(key).map(|v| resolve_script_references(v, self.directory)).transpose();
+    }
+
+    fn required(&mut self, key: &str) -> Result<Supervalue, String> {
+        return self.take(key)?.ok_or_else(|| format!("`{}` is missing `{}`", self.command, key));
+    }
+
+    fn flag(&mut self, key: &str) -> Result<Option<()>, String> {
+        match self.take(key)? {
+            None | Some(Supervalue::Bool(false)) => return Ok(None),
+            Some(Supervalue::Bool(true)) => return Ok(Some(())),
+            Some(_) => return Err(format!("`{}` `{}` must be a boolean", self.command, key)),
+        }
+    }
+
+    fn string(&mut self, key: &str) -> Result<Option<String>, String> {
+        match self.take(key)? {
+            None => return Ok(None),
+            Some(Supervalue::String(v)) => return Ok(Some(v)),
+            Some(_) => return Err(format!("`{}` `{}` must be a string", self.command, key)),
+        }
+    }
+
+    /// A nested list of steps, like the `commands` of `with`.
+    fn commands(&mut self, key: &str) -> Result<Option<Vec<Command>>, String> {
+        let Some(steps) = self.take_raw(key) else {
+            return Ok(None);
+        };
+        return script_commands(
+            steps,
+            self.directory,
+        )
+            .map(Some)
+            .map_err(|(index, e)| format!("Error in step {} of `{}` `{}`: {}", index, self.command, key, e));
+    }
+
+    fn if_body(&mut self) -> Result<IfBody, String> {
+        return Ok(IfBody {
+            commands: self.commands("then")?.ok_or_else(|| format!("`{}` is missing `then`", self.command))?,
+            end: match self.commands("else")? {
+                Some(commands) => IfEnd::Else(ElseBlock {
+                    commands: commands,
+                    end: End::End,
+                }),
+                None => IfEnd::End,
+            },
+        });
+    }
+
+    fn exists_predicate(&mut self) -> Result<ExistsPredicate, String> {
+        return Ok(ExistsPredicate { path: script_path(self.required("path")?)? });
+    }
+
+    fn equals_predicate(&mut self) -> Result<EqualsPredicate, String> {
+        return Ok(EqualsPredicate {
+            path: script_path(self.required("path")?)?,
+            value: script_value(self.required("value")?),
+        });
+    }
+
+    fn schema_valid_predicate(&mut self) -> Result<SchemaValidPredicate, String> {
+        return Ok(SchemaValidPredicate { external: self.take("external")?.map(script_value) });
+    }
+
+    /// Error if there are arguments that weren't read.
+    fn finish(self) -> Result<(), String> {
+        let mut unknown = self.args.into_keys().collect::<Vec<_>>();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        unknown.sort();
+        return Err(format!("`{}` has unknown arguments: {}", self.command, unknown.join(", ")));
+    }
+}
+
+fn script_path(value: Supervalue) -> Result<DataPath, String> {
+    match value {
+        Supervalue::String(v) => return v.parse::<DataPath>(),
+        Supervalue::Vec(v) => return Ok(DataPath(v.value.into_iter().map(|x| x.into()).collect())),
+        _ => return Err(format!("Paths must be strings like `.a.b` or arrays of keys")),
+    }
+}
+
+fn script_paths(value: Supervalue) -> Result<Vec<DataPath>, String> {
+    match value {
+        Supervalue::Vec(v) => return v.value.into_iter().map(script_path).collect(),
+        v => return Ok(vec![script_path(v)?]),
+    }
+}
+
+/// A value in a script, where `{"$var": NAME}` is like `v:NAME`.
+fn script_value(value: Supervalue) -> ValueArg {
+    if let Supervalue::Map(map) = &value {
+        if let (1, Some(Supervalue::String(name))) = (map.value.len(), map.value.get("$var")) {
+            return ValueArg::Variable(name.clone());
+        }
+    }
+    return ValueArg::Value(AargSupervalue {
+        original_format: AargSupervalueOriginalFormat::Json,
+        value: value,
+        source: aargvark::traits_impls::Source::Stdin,
+        schema_directive: None,
+        yaml_anchors: vec![],
+    });
+}
+
+fn script_values(value: Supervalue) -> Vec<ValueArg> {
+    match value {
+        Supervalue::Vec(v) => return v.value.into_iter().map(script_value).collect(),
+        v => return vec![script_value(v)],
+    }
+}
+
+fn is_script_reference(map: &SupervalueMap) -> bool {
+    return map.value.len() == 1 &&
+        ["$file", "$env", "$var"].iter().any(|k| matches!(map.value.get(*k), Some(Supervalue::String(_))));
+}
+
+/// Replace `{"$file": PATH}` (loaded like `a:PATH`, relative to `directory`) and
+/// `{"$env": NAME}` (the variable's value as a string) anywhere in `value`.
+fn resolve_script_references(value: Supervalue, directory: &Path) -> Result<Supervalue, String> {
+    match value {
+        Supervalue::Map(mut map) => {
+            if is_script_reference(&map) {
+                if let Some(Supervalue::String(path)) = map.value.get("$file") {
+                    let path = directory.join(path);
+                    return Ok(AargSupervalue::from_str(&format!("a:{}", path.to_string_lossy()))?.value);
+                }
+                if let Some(Supervalue::String(name)) = map.value.get("$env") {
+                    return std::env::var(
+                        name,
+                    )
+                        .map(Supervalue::String)
+                        .map_err(|e| format!("Error reading environment variable [{}]: {}", name, e));
+                }
+            }
+            for v in map.value.values_mut() {
+                *v = resolve_script_references(std::mem::replace(v, Supervalue::Null), directory)?;
+            }
+            return Ok(Supervalue::Map(map));
+        },
+        Supervalue::Vec(mut vec) => {
+            for v in &mut vec.value {
+                *v = resolve_script_references(std::mem::replace(v, Supervalue::Null), directory)?;
+            }
+            return Ok(Supervalue::Vec(vec));
+        },
+        v => return Ok(v),
+    }
+}
+
+/// Build a command from a script step, like `{"set": {"path": ".a", "data": 4}}`.
+/// Commands with a main list of paths or values (and `get`) also accept just
+/// that in place of the map.
+fn script_command(name: &str, args: Supervalue, directory: &Path) -> Result<Command, String> {
+    let mut args = ScriptArgs {
+        command: name.to_string(),
+        directory: directory,
+        args: match args {
+            Supervalue::Null => HashMap::new(),
+            Supervalue::Map(m) if !is_script_reference(&m) => m.value,
+            v => {
+                let key = match name {
+                    "get" => "path",
+                    "store" | "load" => "name",
+                    "delete" | "keep" | "force-array" => "paths",
+                    "intersect" | "subtract" | "merge" => "values",
+                    _ => return Err(format!("The arguments of `{}` must be a map", name)),
+                };
+                HashMap::from([(key.to_string(), v)])
+            },
+        },
+    };
+    let command = match name {
+        "get" => Command::Get(GetCommand {
+            missing_ok: args.flag("missing-ok")?,
+            path: script_path(args.required("path")?)?,
+        }),
+        "set" => Command::Set(SetCommand {
+            missing_ok: args.flag("missing-ok")?,
+            path: script_path(args.required("path")?)?,
+            data: script_value(args.required("data")?),
+        }),
+        "delete" => Command::Delete(DeleteCommand {
+            missing_ok: args.flag("missing-ok")?,
+            paths: script_paths(args.required("paths")?)?,
+        }),
+        "keep" => Command::Keep(KeepCommand {
+            missing_ok: args.flag("missing-ok")?,
+            paths: script_paths(args.required("paths")?)?,
+        }),
+        "force-array" => Command::ForceArray(ForceArrayCommand { paths: script_paths(args.required("paths")?)? }),
+        "infer-types" => Command::InferTypes,
+        "infer-datetimes" => Command::InferDatetimes,
+        "flatten" | "unflatten" => {
+            let args = FlattenCommand {
+                separator: args.string("separator")?,
+                brackets: args.flag("brackets")?,
+            };
+            if name == "flatten" {
+                Command::Flatten(args)
+            } else {
+                Command::Unflatten(args)
+            }
+        },
+        "search-set" => Command::SearchSet(SearchSetCommand {
+            needle: script_value(args.required("needle")?),
+            data: script_value(args.required("data")?),
+            missing_ok: args.flag("missing-ok")?,
+        }),
+        "search-delete" => Command::SearchDelete(SearchDeleteCommand {
+            needle: script_value(args.required("needle")?),
+            missing_ok: args.flag("missing-ok")?,
+        }),
+        "intersect" => Command::Intersect(IntersectCommand { values: script_values(args.required("values")?) }),
+        "subtract" => Command::Subtract(SubtractCommand {
+            missing_ok: args.flag("missing-ok")?,
+            values: script_values(args.required("values")?),
+        }),
+        "merge" => Command::Merge(MergeCommand { values: script_values(args.required("values")?) }),
+        "validate-json-schema" => Command::ValidateJsonSchema(
+            ValidateJsonSchemaCommand { external: args.take("external")?.map(script_value) },
+        ),
+        "with" => Command::With(WithCommand {
+            missing_ok: args.flag("missing-ok")?,
+            path: script_path(args.required("path")?)?,
+            commands: args.commands("commands")?.ok_or("`with` is missing `commands`")?,
+            end: End::End,
+        }),
+        "if-exists" => Command::IfExists(IfExistsCommand {
+            predicate: args.exists_predicate()?,
+            body: args.if_body()?,
+        }),
+        "if-equals" => Command::IfEquals(IfEqualsCommand {
+            predicate: args.equals_predicate()?,
+            body: args.if_body()?,
+        }),
+        "if-schema-valid" => Command::IfSchemaValid(IfSchemaValidCommand {
+            predicate: args.schema_valid_predicate()?,
+            body: args.if_body()?,
+        }),
+        "assert" => Command::Assert(AssertCommand {
+            message: args.string("message")?,
+            predicate: match args.string("predicate")?.as_deref() {
+                Some("exists") => Predicate::Exists(args.exists_predicate()?),
+                Some("equals") => Predicate::Equals(args.equals_predicate()?),
+                Some("schema-valid") => Predicate::SchemaValid(args.schema_valid_predicate()?),
+                _ => return Err(format!("`assert` `predicate` must be `exists`, `equals` or `schema-valid`")),
+            },
+        }),
+        "store" => Command::Store(VariableCommand { name: args.string("name")?.ok_or("`store` is missing `name`")? }),
+        "load" => Command::Load(VariableCommand { name: args.string("name")?.ok_or("`load` is missing `name`")? }),
+        "codegen" => Command::Codegen(CodegenCommand {
+            schema: args.flag("schema")?,
+            name: args.string("name")?,
+        }),
+        _ => return Err(format!("Unknown command `{}`", name)),
+    };
+    args.finish()?;
+    return Ok(command);
+}
+
+/// Build commands from a list of script steps. Each step is a command name (for
+/// commands without arguments) or a single key map from the command name to its
+/// arguments. Errors have the index of the failing step.
+fn script_commands(steps: Supervalue, directory: &Path) -> Result<Vec<Command>, (usize, String)> {
+    let Supervalue::Vec(steps) = steps else {
+        return Err((0, format!("Commands must be a list")));
+    };
+    let mut out = vec![];
+    for (index, step) in steps.value.into_iter().enumerate() {
+        let res = match step {
+            Supervalue::String(name) => script_command(&name, Supervalue::Null, directory),
+            Supervalue::Map(map) if map.value.len() == 1 => {
+                let (name, args) = map.value.into_iter().next().unwrap();
+                script_command(&name, args, directory)
+            },
+            _ => Err(format!("Steps must be a command name or a map with a single command name key")),
+        };
+        out.push(res.map_err(|e| (index, e))?);
+    }
+    return Ok(out);
+}
+
+/// Load the commands in a `--script` file, a JSON or YAML list of steps.
+fn load_script(path: &Path) -> Result<Vec<Command>, CliError> {
+    let script =
+        AargSupervalue::from_str(&format!("a:{}", path.to_string_lossy())).map_err(CliError::parse)?;
+    let directory = source_directory(&script.source)?;
+    if !matches!(script.value, Supervalue::Vec(_)) {
+        return Err(CliError::parse(format!("Script {:?} must be a list of commands", path)));
+    }
+    return script_commands(
+        script.value,
+        &directory,
+    ).map_err(
+        |(index, e)| CliError::parse(
+            format!("Error in step {} of script {:?}: {}", index, path, e),
+        ).detail("step", json!(index)),
+    );
+}
+
+fn main1(mut root_args: Args) -> Result<(), CliError> {
+    if let Some(path) = &root_args.script {
+        let mut commands = load_script(path)?;
+        commands.extend(std::mem::take(&mut root_args.commands));
+        root_args.commands = commands;
+    }
+    if root_args.output.is_some() && root_args.in_place.is_some() {
+        return Err(CliError::parse("`--output` and `--in-place` can't be used together"));
+    }
+    let mut sources = std::mem::take(&mut root_args.source.0);
+    for more in std::mem::take(&mut root_args.more_sources) {
+        sources.extend(more.0);
+    }
+    if sources.len() == 1 {
+        let (_, source) = sources.pop().unwrap();
+        return process(&root_args, source?, None);
+    }
+    if root_args.output.is_some() {
+        return Err(CliError::parse("`--output` can't be used with multiple sources"));
+    }
+
+    // Process each, reporting results (in the final error only, for the JSON error
+    // format)
+    let report = root_args.error_format != Some(ErrorFormat::Json);
+    let count = sources.len();
+    let mut failures = vec![];
+    for (i, (name, source)) in sources.into_iter().enumerate() {
+        match source.and_then(|source| process(&root_args, source, Some(i))) {
+            Ok(_) => {
+                if report {
+                    eprintln!("{}: ok", name);
+                }
+            },
+            Err(e) => {
+                if report {
+                    eprintln!("{}: {}", name, e.message);
+                }
+                failures.push((name, e));
+            },
+        }
+    }
+    if report {
+        eprintln!("{} of {} sources succeeded", count - failures.len(), count);
+    }
+    let Some((_, first)) = failures.first() else {
+        return Ok(());
+    };
+    return Err(
+        CliError::new(
+            first.category,
+            format!("{} of {} sources failed", failures.len(), count),
+        ).detail("failures", serde_json::Value::Array(failures.iter().map(|(name, e)| json!({
+            "source": name,
+            "category": e.category.name(),
+            "message": e.message,
+        })).collect())),
+    );
+}
+
+/// Validate `at` against `external`, or else the schema referred to by `$schema`
+/// in `at` or the source's comment directive, returning the validation errors
+/// (each with the `instance_path`, `schema_path` and `message`).
+fn schema_errors(
+    source: &AargSupervalue,
+    at: &Supervalue,
+    external: Option<&Supervalue>,
+) -> Result<Vec<serde_json::Value>, CliError> {
+    let working_directory = source_directory(&source.source)?;
+    let schema: serde_json::Value = if let Some(schema) = external {
+        schema.clone().into()
+    } else if let Ok(Supervalue::String(addr)) = at.get_path(&DataPath(vec![json!("$schema")])) {
+        load_schema(addr, None)?
+    } else if let Some(addr) = &source.schema_directive {
+        load_schema(addr, Some(&working_directory))?
+    } else {
+        return Err(
+            CliError::parse(
+                "The data doesn't contain `$schema` or a schema comment directive and no external schema specified, cannot validate",
+            ),
+        );
+    };
+
+    struct MyRetriever {
+        working_directory: PathBuf,
+    }
+
+    impl jsonschema::Retrieve for MyRetriever {
+        fn retrieve(
+            &self,
+            uri: &jsonschema::Uri<String>,
+        ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+            match uri.scheme().as_str() {
+                "http" | "https" => {
+                    return Ok(
+                        ureq::get(uri.as_str())
+                            .call()
+                            .map_err(
+                                |e| format!(
+                                    "Error sending request for external resource at [{}]: {}",
+                                    uri,
+                                    e
+                                ),
+                            )?
+                            .body_mut()
+                            .read_json()
+                            .map_err(
+                                |e| format!(
+                                    "Error reading JSON from external resource response at [{}]: {}",
+                                    uri,
+                                    e
+                                ),
+                            )?,
+                    );
+                },
+                "json-schema" => {
+                    let path = self.working_directory.join(uri.path().as_str().trim_start_matches("/"));
+                    return Ok(
+                        serde_json::from_slice(
+                            &std::fs::read(
+                                &path,
+                            ).map_err(
+                                |e| format!("Error reading external resource at [{:?}]: {}", path, e),
+                            )?,
+                        )?,
+                    );
+                },
+                "file" | "" => {
+                    let path = self.working_directory.join(uri.path().as_str());
+                    return Ok(
+                        serde_json::from_slice(
+                            &std::fs::read(
+                                &path,
+                            ).map_err(
+                                |e| format!("Error reading external resource at [{:?}]: {}", path, e),
+                            )?,
+                        )?,
+                    );
+                },
+                scheme => {
+                    return Err(
+                        std::io::Error::other(
+                            format!("Unimplemented resource url scheme: {}", scheme),
+                        ).into(),
+                    );
+                },
+            }
+        }
+    }
+
+    let validator =
+        Validator::options()
+            .with_retriever(MyRetriever { working_directory: working_directory })
+            .build(&schema)
+            .map_err(|e| CliError::parse(format!("Error interpreting JSON Schema as JSON Schema: {}", e)))?;
+    let instance = at.clone().into();
+    return Ok(validator.iter_errors(&instance).map(|e| json!({
+        "instance_path": e.instance_path.to_string(),
+        "schema_path": e.schema_path.to_string(),
+        "message": e.to_string(),
+    })).collect());
+}
+
+/// A validation error for `errors` from `schema_errors`, with `message` or the
+/// error messages.
+fn validation_error(message: Option<&str>, errors: Vec<serde_json::Value>) -> CliError {
+    let message = match message {
+        Some(m) => m.to_string(),
+        None => errors.iter().filter_map(|e| e["message"].as_str()).collect::<Vec<_>>().join("\n"),
+    };
+    return CliError::new(ErrorCategory::Validation, message).detail("errors", serde_json::Value::Array(errors));
+}
+
+impl ExistsPredicate {
+    fn test(&self, at: &Supervalue) -> bool {
+        return at.get_path(&self.path).is_ok();
+    }
+}
+
+impl EqualsPredicate {
+    fn test(&self, at: &Supervalue, variables: &HashMap<String, Supervalue>) -> Result<bool, CliError> {
+        let value = self.value.get(variables)?;
+        return Ok(at.get_path(&self.path).is_ok_and(|v| v == value));
+    }
+}
+
+impl SchemaValidPredicate {
+    fn errors(
+        &self,
+        source: &AargSupervalue,
+        at: &Supervalue,
+        variables: &HashMap<String, Supervalue>,
+    ) -> Result<Vec<serde_json::Value>, CliError> {
+        let external = self.external.as_ref().map(|v| v.get(variables)).transpose()?;
+        return schema_errors(source, at, external);
+    }
+}
+
+/// Whether `predicate` holds for `at`, with the schema validation errors if it's
+/// `schema-valid`.
+fn test_predicate(
+    source: &AargSupervalue,
+    predicate: &Predicate,
+    at: &Supervalue,
+    variables: &HashMap<String, Supervalue>,
+) -> Result<(bool, Vec<serde_json::Value>), CliError> {
+    match predicate {
+        Predicate::Exists(p) => return Ok((p.test(at), vec![])),
+        Predicate::Equals(p) => return Ok((p.test(at, variables)?, vec![])),
+        Predicate::SchemaValid(p) => {
+            let errors = p.errors(source, at, variables)?;
+            return Ok((errors.is_empty(), errors));
+        },
+    }
+}
+
+/// Run the `then` or `else` commands of an `if-*` command.
+fn run_if(
+    source: &AargSupervalue,
+    condition: bool,
+    body: &IfBody,
+    at: Supervalue,
+    variables: &mut HashMap<String, Supervalue>,
+) -> Result<Supervalue, CliError> {
+    match (condition, &body.end) {
+        (true, _) => return run_commands(source, &body.commands, at, variables),
+        (false, IfEnd::Else(else_)) => return run_commands(source, &else_.commands, at, variables),
+        (false, IfEnd::End) => return Ok(at),
+    }
+}
+
+/// Run `commands` in order on `at`, a value from `source` (or within it, for
+/// `with`).
+fn run_commands(
+    source: &AargSupervalue,
+    commands: &[Command],
+    mut at: Supervalue,
+    variables: &mut HashMap<String, Supervalue>,
+) -> Result<Supervalue, CliError> {
+    for command in commands {
         match command {
             Command::Get(args) => {
                 at = get(&mut at, &args.path, args.missing_ok.is_some())?.unwrap_or(Supervalue::Null);
             },
             Command::Set(args) => {
-                set(&mut at, &args.path, &args.data.value, args.missing_ok.is_some())?;
+                set(&mut at, &args.path, args.data.get(&variables)?, args.missing_ok.is_some())?;
             },
             Command::Delete(args) => {
-                for path in args.paths {
+                for path in &args.paths {
                     delete(&mut at, &path, args.missing_ok.is_some())?;
                 }
             },
             Command::Keep(args) => {
                 let mut out = None;
-                for path in args.paths {
-                    keep(&mut at, &mut out, &path, args.missing_ok.is_some())?;
+                for path in &args.paths {
+                    keep(&mut at, &mut out, path, args.missing_ok.is_some())?;
                 }
                 at = out.unwrap_or(Supervalue::Null);
             },
+            Command::ForceArray(args) => {
+                for path in &args.paths {
+                    force_array(&mut at, path);
+                }
+            },
+            Command::InferTypes => {
+                infer_types(&mut at);
+            },
+            Command::InferDatetimes => {
+                infer_datetimes(&mut at);
+            },
+            Command::Flatten(args) => {
+                at = flatten(at, &args.options())?;
+            },
+            Command::Unflatten(args) => {
+                at = unflatten(at, &args.options())?;
+            },
             Command::SearchSet(args) => {
-                let change_count = search_set(&mut at, &args.needle.value, &args.data.value);
+                let needle = args.needle.get(&variables)?;
+                let change_count = search_set(&mut at, needle, args.data.get(&variables)?);
                 if args.missing_ok.is_none() && change_count == 0 {
                     return Err(
-                        format!(
-                            "No changes made; couldn't find needle {}",
-                            serde_json::to_string(
-                                &<Supervalue as Into<serde_json::Value>>::into(args.needle.value),
-                            ).unwrap()
-                        ),
+                        CliError::from(Error::NeedleNotFound { needle: needle.clone() }).context("No changes made"),
                     );
                 }
             },
             Command::SearchDelete(args) => {
-                let change_count = search_delete(&mut at, &args.needle.value);
+                let needle = args.needle.get(&variables)?;
+                let change_count = search_delete(&mut at, needle);
                 if args.missing_ok.is_none() && change_count == 0 {
                     return Err(
-                        format!(
-                            "No changes made; couldn't find needle {}",
-                            serde_json::to_string(
-                                &<Supervalue as Into<serde_json::Value>>::into(args.needle.value),
-                            ).unwrap()
-                        ),
+                        CliError::from(Error::NeedleNotFound { needle: needle.clone() }).context("No changes made"),
                     );
                 }
             },
             Command::Intersect(args) => {
-                for other in args.values {
-                    intersect(&mut at, &other.value);
+                for other in &args.values {
+                    intersect(&mut at, other.get(&variables)?);
                 }
             },
             Command::Subtract(args) => {
                 for (layer_index, arg) in args.values.iter().enumerate() {
-                    if let Err(e) = subtract(&mut at, &arg.value, args.missing_ok.is_some()) {
-                        return Err(format!("Failed to subtract layer {}:\n{}", layer_index, e));
+                    if let Err(e) = subtract(&mut at, arg.get(&variables)?, args.missing_ok.is_some()) {
+                        return Err(
+                            CliError::from(e)
+                                .context(format!("Failed to subtract layer {}", layer_index))
+                                .detail("layer", json!(layer_index)),
+                        );
                     }
                 }
             },
             Command::Merge(args) => {
-                for v in args.values {
-                    merge(&mut at, v.value);
+                for v in &args.values {
+                    merge(&mut at, v.get(&variables)?.clone());
                 }
             },
             Command::ValidateJsonSchema(args) => {
-                let schema: serde_json::Value = if let Some(schema) = args.external {
-                    schema.value.into()
-                } else if let Some(Supervalue::String(addr)) =
-                    get(&mut at, &DataPath(vec![json!("$schema")]), true)? {
-                    if addr.starts_with("https://") || addr.starts_with("http:///") {
-                        ureq::get(addr.as_str())
-                            .call()
-                            .map_err(|e| format!("Error sending request for external schema at [{}]: {}", addr, e))?
-                            .body_mut()
-                            .read_json()
-                            .map_err(
-                                |e| format!("Error reading JSON from external schema response at [{}]: {}", addr, e),
-                            )?
-                    } else {
-                        let schema =
-                            std::fs::read(
-                                &addr,
-                            ).map_err(|e| format!("Error loading schema [{}] from disk: {}", addr, e))?;
-                        let schema =
-                            serde_json::from_slice::<serde_json::Value>(
-                                &schema,
-                            ).map_err(|e| format!("Schema at [{}] is invalid JSON: {}", addr, e))?;
-                        schema
-                    }
-                } else {
-                    return Err(
-                        format!("The data doesn't contain `$schema` and no external schema specified, cannot validate"),
-                    );
-                };
-
-                struct MyRetriever {
-                    working_directory: PathBuf,
+                let external = args.external.as_ref().map(|v| v.get(variables)).transpose()?;
+                let errors = schema_errors(source, &at, external)?;
+                if !errors.is_empty() {
+                    return Err(validation_error(None, errors));
                 }
-
-                impl jsonschema::Retrieve for MyRetriever {
-                    fn retrieve(
-                        &self,
-                        uri: &jsonschema::Uri<String>,
-                    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-                        match uri.scheme().as_str() {
-                            "http" | "https" => {
-                                return Ok(
-                                    ureq::get(uri.as_str())
-                                        .call()
-                                        .map_err(
-                                            |e| format!(
-                                                "Error sending request for external resource at [{}]: {}",
-                                                uri,
-                                                e
-                                            ),
-                                        )?
-                                        .body_mut()
-                                        .read_json()
-                                        .map_err(
-                                            |e| format!(
-                                                "Error reading JSON from external resource response at [{}]: {}",
-                                                uri,
-                                                e
-                                            ),
-                                        )?,
-                                );
-                            },
-                            "json-schema" => {
-                                let path = self.working_directory.join(uri.path().as_str().trim_start_matches("/"));
-                                return Ok(
-                                    serde_json::from_slice(
-                                        &std::fs::read(
-                                            &path,
-                                        ).map_err(
-                                            |e| format!("Error reading external resource at [{:?}]: {}", path, e),
-                                        )?,
-                                    )?,
-                                );
-                            },
-                            "file" | "" => {
-                                let path = self.working_directory.join(uri.path().as_str());
-                                return Ok(
-                                    serde_json::from_slice(
-                                        &std::fs::read(
-                                            &path,
-                                        ).map_err(
-                                            |e| format!("Error reading external resource at [{:?}]: {}", path, e),
-                                        )?,
-                                    )?,
-                                );
-                            },
-                            scheme => {
-                                return Err(
-                                    std::io::Error::other(
-                                        format!("Unimplemented resource url scheme: {}", scheme),
-                                    ).into(),
-                                );
-                            },
-                        }
-                    }
+            },
+            Command::With(args) => {
+                // The source's schema directive is for the whole document, not the subtree
+                let scoped = AargSupervalue {
+                    original_format: source.original_format,
+                    value: Supervalue::Null,
+                    source: source.source.clone(),
+                    schema_directive: None,
+                    yaml_anchors: vec![],
+                };
+                match at.get_path_mut(&args.path) {
+                    Ok(sub) => {
+                        *sub =
+                            run_commands(
+                                &scoped,
+                                &args.commands,
+                                std::mem::replace(sub, Supervalue::Null),
+                                variables,
+                            ).map_err(|e| e.context(format!("In `with` at {}", args.path)))?;
+                    },
+                    Err(_) if args.missing_ok.is_some() => { },
+                    Err(e) => return Err(e.into()),
                 }
-
-                let validator =
-                    Validator::options()
-                        .with_retriever(MyRetriever { working_directory: match &root_args.source.source {
-                            aargvark::traits_impls::Source::Stdin => current_dir().map_err(
-                                |e| format!(
-                                    "No file source to root relative paths and couldn't determine working directory from executable working directory: {}",
-                                    e
-                                ),
-                            )?,
-                            aargvark::traits_impls::Source::File(v) => v
-                                .canonicalize()
-                                .map_err(
-                                    |e| format!(
-                                        "Error determining absolute path of source [{}]: {}",
-                                        v.to_string_lossy(),
-                                        e
-                                    ),
-                                )?
-                                .parent()
-                                .ok_or_else(
-                                    || format!(
-                                        "Could not determine parent directory of source file [{}]",
-                                        v.to_string_lossy()
-                                    ),
-                                )?
-                                .to_path_buf(),
-                        } })
-                        .build(&schema)
-                        .map_err(|e| format!("Error interpreting JSON Schema as JSON Schema: {}", e))?;
-                if let Err(e) = validator.validate(&at.clone().into()) {
-                    eprintln!("{}", e);
-                    exit(1);
+            },
+            Command::IfExists(args) => {
+                let condition = args.predicate.test(&at);
+                at = run_if(source, condition, &args.body, at, variables)?;
+            },
+            Command::IfEquals(args) => {
+                let condition = args.predicate.test(&at, variables)?;
+                at = run_if(source, condition, &args.body, at, variables)?;
+            },
+            Command::IfSchemaValid(args) => {
+                let condition = args.predicate.errors(source, &at, variables)?.is_empty();
+                at = run_if(source, condition, &args.body, at, variables)?;
+            },
+            Command::Assert(args) => {
+                let (holds, errors) = test_predicate(source, &args.predicate, &at, variables)?;
+                if !holds {
+                    let message = args.message.clone().unwrap_or_else(|| match &args.predicate {
+                        Predicate::Exists(p) => format!("Assertion failed: {} doesn't exist", p.path),
+                        Predicate::Equals(p) => format!("Assertion failed: {} doesn't equal the expected value", p.path),
+                        Predicate::SchemaValid(_) => format!("Assertion failed: the data isn't valid against the schema"),
+                    });
+                    return Err(validation_error(Some(&message), errors));
                 }
             },
+            Command::Store(args) => {
+                variables.insert(args.name.clone(), at.clone());
+            },
+            Command::Load(args) => {
+                at = get_variable(&variables, &args.name)?.clone();
+            },
+            Command::Codegen(args) => {
+                let name = args.name.as_deref().unwrap_or("Root");
+                let code = if args.schema.is_some() {
+                    codegen_schema(&at.into(), name).map_err(CliError::parse)?
+                } else {
+                    codegen_value(&at, name)
+                };
+                at = Supervalue::String(code);
+            },
         }
     }
-    let v = superif!({
+    return Ok(at);
+}
+
+/// Run the commands on the source and write the result. `batch_index` is the
+/// index of the source when there are multiple.
+fn process(root_args: &Args, mut source: AargSupervalue, batch_index: Option<usize>) -> Result<(), CliError> {
+    let mut at = std::mem::replace(&mut source.value, Supervalue::Null);
+    if root_args.yaml_merge_keys.is_some() && source.original_format == AargSupervalueOriginalFormat::Yaml {
+        at = resolve_merge_keys(at).map_err(CliError::parse)?;
+    }
+    let mut at = run_commands(&source, &root_args.commands, at, &mut HashMap::new())?;
+    let output = match &root_args.output {
+        Some(p) if p.is_relative() => Some(source_directory(&source.source)?.join(p)),
+        Some(p) => Some(p.clone()),
+        None => None,
+    };
+    let format = match root_args.format {
+        Some(f) => f,
+        None => match output.as_ref().and_then(|p| output_format(p)) {
+            Some(f) => default_format(f),
+            None => default_format(source.original_format),
+        },
+    };
+    let tag_style = match root_args.tag_style.unwrap_or_default() {
+        TagStyle::Map => LibTagStyle::Map,
+        TagStyle::Key => LibTagStyle::Key,
+        TagStyle::Value => LibTagStyle::Value,
+    };
+    match format {
+        Format::Yaml => {
+            if tag_style == LibTagStyle::Key {
+                at = tags_from_keys(at);
+            }
+        },
+        Format::Cbor | Format::Msgpack => {
+            // Tags and extension values are written natively
+        },
+        _ => {
+            at = tags_to_style(at, tag_style);
+        },
+    }
+    let convert_options = ConvertOptions {
+        lossy_numbers: root_args.lossy_numbers.is_some(),
+        toml_null: match root_args.toml_null.unwrap_or_default() {
+            TomlNull::Omit => LibTomlNull::Omit,
+            TomlNull::Error => LibTomlNull::Error,
+            TomlNull::Empty => LibTomlNull::Empty,
+        },
+    };
+    let csv_columns = match &root_args.csv_columns {
+        Some(c) => Some(
+            parse_csv_columns(c, b',').map_err(|e| CliError::parse(format!("Invalid `--csv-columns`: {}", e)))?,
+        ),
+        None => None,
+    };
+    let unquoted = root_args.unquote.is_some() && matches!(at, Supervalue::String(_));
+    let mut v = superif!({
         if !root_args.unquote.is_some() {
             break 'quote;
         }
         let Supervalue::String(at) = at else {
             break 'quote;
         };
-        at
+        at.into_bytes()
     } 'quote {
-        match root_args.format.unwrap_or(match root_args.source.original_format {
-            AargSupervalueOriginalFormat::Json => Format::PrettyJson,
-            AargSupervalueOriginalFormat::Yaml => Format::Yaml,
-            AargSupervalueOriginalFormat::Toml => Format::Toml,
-        }) {
+        match format {
             Format::CompactJson => {
-                serde_json::to_string(&<Supervalue as Into::<serde_json::Value>>::into(at)).unwrap()
+                serde_json::to_vec(
+                    &at.to_json_value(&convert_options).map_err(CliError::conversion)?,
+                ).map_err(|e| CliError::conversion(format!("Error converting result to JSON: {}", e)))?
             },
             Format::PrettyJson => {
                 format!(
                     "{}\n",
-                    serde_json::to_string_pretty(&<Supervalue as Into::<serde_json::Value>>::into(at)).unwrap()
-                )
+                    serde_json::to_string_pretty(
+                        &at.to_json_value(&convert_options).map_err(CliError::conversion)?,
+                    ).map_err(|e| CliError::conversion(format!("Error converting result to JSON: {}", e)))?
+                ).into_bytes()
             },
+            Format::Json5 => to_json5_string_pretty(&at).into_bytes(),
             Format::Toml => {
-                toml::to_string_pretty(&<Supervalue as Into::<toml::Value>>::into(at)).unwrap()
+                toml::to_string_pretty(
+                    &at.to_toml_value(&convert_options).map_err(CliError::conversion)?,
+                ).map_err(|e| CliError::conversion(format!("Error converting result to TOML: {}", e)))?.into_bytes()
             },
             Format::Yaml => {
-                serde_yaml::to_string(&<Supervalue as Into::<serde_yaml::Value>>::into(at)).unwrap()
+                if root_args.yaml_anchors.is_some() {
+                    to_yaml_string_with_anchors(
+                        &at,
+                        &source.yaml_anchors,
+                        &convert_options,
+                    ).map_err(|e| CliError::conversion(format!("Error converting result to YAML: {}", e)))?.into_bytes()
+                } else {
+                    serde_yaml::to_string(
+                        &at.to_yaml_value(&convert_options).map_err(CliError::conversion)?,
+                    ).map_err(|e| CliError::conversion(format!("Error converting result to YAML: {}", e)))?.into_bytes()
+                }
+            },
+            Format::Ron => {
+                format!(
+                    "{}\n",
+                    ron::ser::to_string_pretty(
+                        &at.to_ron_value(&convert_options).map_err(CliError::conversion)?,
+                        ron::ser::PrettyConfig::default(),
+                    ).map_err(|e| CliError::conversion(format!("Error converting result to RON: {}", e)))?
+                ).into_bytes()
             },
+            Format::Xml => to_xml_string(
+                &at,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to XML: {}", e)))?.into_bytes(),
+            Format::Csv => to_csv_string(
+                &at,
+                b',',
+                csv_columns.as_deref(),
+            ).map_err(|e| CliError::conversion(format!("Error converting result to CSV: {}", e)))?.into_bytes(),
+            Format::Tsv => to_csv_string(
+                &at,
+                b'\t',
+                csv_columns.as_deref(),
+            ).map_err(|e| CliError::conversion(format!("Error converting result to TSV: {}", e)))?.into_bytes(),
+            Format::Env => to_env_string(
+                &at,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to .env: {}", e)))?.into_bytes(),
+            Format::Ini => to_ini_string(
+                &at,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to INI: {}", e)))?.into_bytes(),
+            Format::Properties => to_properties_string(
+                &at,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to properties: {}", e)))?.into_bytes(),
+            Format::Cbor => to_cbor_vec(
+                &at,
+                &convert_options,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to CBOR: {}", e)))?,
+            Format::Msgpack => to_msgpack_vec(
+                &at,
+                &convert_options,
+            ).map_err(|e| CliError::conversion(format!("Error converting result to MessagePack: {}", e)))?,
         }
     });
-    if root_args.in_place.is_some() {
-        let aargvark::traits_impls::Source::File(p) = &root_args.source.source else {
-            return Err("Requested in-place modification but source is not a filesystem path".to_string());
+    let destination = match (output, &source.source) {
+        (Some(p), _) => Some(p),
+        (None, aargvark::traits_impls::Source::File(p)) if root_args.in_place.is_some() || root_args.check.is_some() => Some(
+            p.clone(),
+        ),
+        (None, _) => None,
+    };
+    if root_args.check.is_some() {
+        let Some(p) = &destination else {
+            return Err(CliError::parse("Requested check but source is not a filesystem path"));
         };
-        write(&p, v.as_bytes()).map_err(|e| format!("Error writing result to {:?}: {}", p, e))?;
+        let original = match std::fs::read(&p) {
+            Ok(v) => v,
+            Err(e) if root_args.output.is_some() && e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(CliError::io(format!("Error reading {:?}: {}", p, e))),
+        };
+        let name = root_args.output.as_ref().unwrap_or(p).to_string_lossy();
+        if let Some(diff) = diff_bytes(&name, &original, &v) {
+            std::io::stdout()
+                .write_all(diff.as_bytes())
+                .map_err(|e| CliError::io(format!("Error writing diff to stdout: {}", e)))?;
+            return Err(
+                CliError::new(
+                    ErrorCategory::Changed,
+                    format!("{:?} would be changed", p),
+                ).detail("path", json!(p.to_string_lossy())),
+            );
+        }
+    } else if let Some(p) = &destination {
+        write_atomic(&p, &v, &AtomicWriteOptions {
+            symlinks: match root_args.symlinks.unwrap_or_default() {
+                Symlinks::Follow => LibSymlinks::Follow,
+                Symlinks::Refuse => LibSymlinks::Refuse,
+            },
+            backup_suffix: root_args.backup_suffix.clone(),
+        }).map_err(|e| CliError::io(format!("Error writing result to {:?}: {}", p, e)))?;
     } else {
-        print!("{}", v);
+        if root_args.in_place.is_some() {
+            return Err(CliError::parse("Requested in-place modification but source is not a filesystem path"));
+        }
+        if batch_index.is_some() {
+            // Delimit the results from each source
+            if unquoted || format == Format::CompactJson {
+                v.push(b'\n');
+            } else if format == Format::Yaml {
+                v.splice(0 .. 0, b"---\n".iter().copied());
+            } else if !matches!(format, Format::PrettyJson | Format::Json5) {
+                return Err(
+                    CliError::parse(
+                        "Results from multiple sources can only be written to stdout as JSON, JSON5 or YAML, use `--format` or write to the files with `-i`",
+                    ),
+                );
+            }
+        }
+        std::io::stdout().write_all(&v).map_err(|e| CliError::io(format!("Error writing result to stdout: {}", e)))?;
     }
     return Ok(());
 }
 
 fn main() {
-    match main1() {
+    // Determined separately so argument errors can also be formatted
+    let raw_args = std::env::args().collect::<Vec<_>>();
+    let mut error_format =
+        match raw_args.windows(2).find(|w| w[0] == "--error-format").map(|w| w[1].as_str()) {
+            Some("json") => ErrorFormat::Json,
+            _ => ErrorFormat::Text,
+        };
+    let res = match parse_args() {
+        Ok(args) => {
+            error_format = args.error_format.clone().unwrap_or_default();
+            main1(args)
+        },
+        Err(e) => Err(e),
+    };
+    match res {
         Ok(_) => { },
         Err(e) => {
-            eprintln!("Exiting with fatal error: {}", e);
-            exit(1);
+            match error_format {
+                ErrorFormat::Text => {
+                    eprintln!("Exiting with fatal error: {}", e.message);
+                },
+                ErrorFormat::Json => {
+                    let mut out = e.details;
+                    out.insert("category".to_string(), json!(e.category.name()));
+                    out.insert("exit_code".to_string(), json!(e.category.exit_code()));
+                    out.insert("message".to_string(), json!(e.message));
+                    eprintln!("{}", serde_json::Value::Object(out));
+                },
+            }
+            exit(e.category.exit_code());
         },
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            error_category,
+            run_commands,
+            script_command,
+            script_commands,
+            Args,
+            Command,
+            ErrorCategory,
+            SourceArg,
+        },
+        aargvark::{
+            traits_impls::AargvarkFromStr,
+            vark_explicit,
+            VarkRet,
+        },
+        hammer_of_json::error::Error,
+        hammer_of_json::supervalue::{
+            AargSupervalue,
+            AargSupervalueOriginalFormat,
+            Supervalue,
+        },
+        serde_json::json,
+        std::{
+            collections::HashMap,
+            path::Path,
+        },
+    };
+
+    fn source(value: serde_json::Value) -> AargSupervalue {
+        return AargSupervalue {
+            original_format: AargSupervalueOriginalFormat::Json,
+            value: value.into(),
+            source: aargvark::traits_impls::Source::Stdin,
+            schema_directive: None,
+            yaml_anchors: vec![],
+        };
+    }
+
+    /// Run script steps on `value`.
+    fn run_script(steps: serde_json::Value, directory: &Path, value: serde_json::Value) -> serde_json::Value {
+        let commands = script_commands(steps.into(), directory).map_err(|(_, e)| e).unwrap();
+        let source = source(value);
+        let at = source.value.clone();
+        return run_commands(&source, &commands, at, &mut HashMap::new()).map_err(|e| e.message).unwrap().into();
+    }
+
+    /// Parse command line arguments and run the commands on the (single) source.
+    fn run_args(args: &[&str]) -> Result<serde_json::Value, String> {
+        let VarkRet::Ok(args) =
+            vark_explicit::<Args>(None, args.iter().map(|a| a.to_string()).collect()).map_err(|e| e.to_string())? else {
+                unreachable!();
+            };
+        let (_, source) = args.source.0.into_iter().next().unwrap();
+        let source = source.map_err(|e| e.message)?;
+        let at = source.value.clone();
+        return Ok(run_commands(&source, &args.commands, at, &mut HashMap::new()).map_err(|e| e.message)?.into());
+    }
+
+    #[test]
+    fn error_categories() {
+        let SourceArg(sources) = SourceArg::from_str("f:/nonexistent/hoj.json").ok().unwrap();
+        assert!(matches!(sources.as_slice(), [(_, Err(e))] if e.category == ErrorCategory::Io));
+        assert!(SourceArg::from_str("[1,").is_err());
+        let conflict = Error::FlattenConflict { key: "a".to_string() };
+        assert!(error_category(&conflict) == ErrorCategory::Conversion);
+        assert!(
+            error_category(
+                &Error::Multiple(vec![Error::NeedleNotFound { needle: Supervalue::Null }, conflict]),
+            ) == ErrorCategory::NeedleNotFound
+        );
+    }
+
+    #[test]
+    fn source_patterns() {
+        let e = SourceArg::from_str("[1,").err().unwrap();
+        assert!(e.starts_with("Inline json [[1,] is invalid"), "{}", e);
+        let e = SourceArg::from_str("[1,*").err().unwrap();
+        assert!(e.starts_with("Inline json [[1,*] is invalid"), "{}", e);
+        assert_eq!(
+            SourceArg::from_str("f:/nonexistent/*.json").err(),
+            Some("File pattern [/nonexistent/*.json] didn't match any files".to_string())
+        );
+        let SourceArg(sources) =
+            SourceArg::from_str(&format!("fs:{}/src/env_*.rs", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert_eq!(sources.len(), 1);
+        assert!(sources[0].0.ends_with("env_file.rs"));
+        assert!(matches!(sources[0].1, Ok(_)));
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            run_args(
+                &[
+                    r#"{"a": {"x": 1}, "b": {}}"#,
+                    "store",
+                    "root",
+                    "get",
+                    ".a",
+                    "store",
+                    "a",
+                    "load",
+                    "root",
+                    "set",
+                    ".b",
+                    "v:a",
+                ],
+            ),
+            Ok(json!({
+                "a": {
+                    "x": 1
+                },
+                "b": {
+                    "x": 1
+                }
+            }))
+        );
+        assert_eq!(
+            run_args(&[r#"{"a": 1}"#, "store", "x", "set", ".a", "2", "merge", "v:x", r#"{"b": 3}"#]),
+            Ok(json!({
+                "a": 1,
+                "b": 3
+            }))
+        );
+        assert!(run_args(&["{}", "load", "x"]).unwrap_err().contains("[x]"));
+        assert!(run_args(&["{}", "set", ".a", "v:x"]).unwrap_err().contains("[x]"));
+    }
+
+    #[test]
+    fn with_schema_directive() {
+        let dir = std::env::temp_dir().join(format!("hoj-with-schema-directive-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("schema.json"), r#"{"required": ["a"]}"#).unwrap();
+        std::fs::write(dir.join("data.yaml"), "# yaml-language-server: $schema=./schema.json\na:\n  b: 1\n").unwrap();
+        let data = format!("a:{}", dir.join("data.yaml").to_string_lossy());
+        assert!(run_args(&[&data, "validate-json-schema"]).is_ok());
+        let error = run_args(&[&data, "with", ".a", "validate-json-schema", "end"]).unwrap_err();
+        assert!(error.contains("doesn't contain `$schema`"));
+        assert!(run_args(&[&data, "with", ".a", "validate-json-schema", "--external", "{}", "end"]).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn conditions() {
+        let data = r#"{"a": {"b": 1}}"#;
+        assert_eq!(
+            run_args(&[data, "if-exists", ".a.b", "set", "-m", ".x", "1", "else", "set", "-m", ".x", "2", "end"]),
+            Ok(json!({
+                "a": {
+                    "b": 1
+                },
+                "x": 1
+            }))
+        );
+        assert_eq!(
+            run_args(&[data, "if-exists", ".a.c", "set", "-m", ".x", "1", "else", "set", "-m", ".x", "2", "end"]),
+            Ok(json!({
+                "a": {
+                    "b": 1
+                },
+                "x": 2
+            }))
+        );
+
+        // Missing paths aren't equal to anything, including null
+        assert_eq!(run_args(&[data, "if-equals", ".a.c", "null", "set", "-m", ".x", "1", "end"]), Ok(json!({
+            "a": {
+                "b": 1
+            }
+        })));
+        assert_eq!(run_args(&[data, "if-equals", ".a.b", "1", "get", ".a", "end"]), Ok(json!({
+            "b": 1
+        })));
+        assert_eq!(
+            run_args(&[data, "if-schema-valid", "--external", r#"{"required": ["b"]}"#, "delete", ".a", "end"]),
+            Ok(json!({
+                "a": {
+                    "b": 1
+                }
+            }))
+        );
+
+        // Nested blocks each consume their own `end`
+        assert_eq!(
+            run_args(
+                &[
+                    data,
+                    "if-exists",
+                    ".a",
+                    "with",
+                    ".a",
+                    "if-equals",
+                    ".b",
+                    "1",
+                    "set",
+                    "-m",
+                    ".c",
+                    "2",
+                    "end",
+                    "end",
+                    "else",
+                    "set",
+                    "-m",
+                    ".d",
+                    "3",
+                    "end",
+                    "set",
+                    "-m",
+                    ".e",
+                    "4",
+                ],
+            ),
+            Ok(json!({
+                "a": {
+                    "b": 1,
+                    "c": 2
+                },
+                "e": 4
+            }))
+        );
+        assert_eq!(run_script(json!([
+            {
+                "if-equals": {
+                    "path": ".a.b",
+                    "value": 2,
+                    "then": [{
+                        "set": {
+                            "path": ".x",
+                            "data": 1,
+                            "missing-ok": true
+                        }
+                    }],
+                    "else": ["infer-types", {
+                        "get": ".a"
+                    }]
+                }
+            },
+        ]), Path::new("."), json!({
+            "a": {
+                "b": 1
+            }
+        })), json!({
+            "b": 1
+        }));
+    }
+
+    #[test]
+    fn assert() {
+        let data = r#"{"a": {"b": 1}}"#;
+        assert!(run_args(&[data, "assert", "exists", ".a.b"]).is_ok());
+        assert!(run_args(&[data, "assert", "equals", ".a.b", "1"]).is_ok());
+        assert!(run_args(&[data, "assert", "equals", ".a.c", "null"]).is_err());
+        assert_eq!(
+            run_args(&[data, "assert", "--message", "b must be 2", "equals", ".a.b", "2"]),
+            Err("b must be 2".to_string())
+        );
+        assert!(run_args(&[data, "assert", "schema-valid", "--external", r#"{"required": ["x"]}"#]).is_err());
+    }
+
+    #[test]
+    fn script_steps() {
+        let commands = script_commands(Supervalue::from(json!([
+            "infer-types",
+            {
+                "set": {
+                    "path": ".a",
+                    "data": 4
+                }
+            },
+            {
+                "delete": [".b", ".c"]
+            },
+            {
+                "get": ".a"
+            },
+        ])), Path::new(".")).map_err(|(_, e)| e).unwrap();
+        assert!(matches!(commands.as_slice(), [
+            Command::InferTypes,
+            Command::Set(_),
+            Command::Delete(d),
+            Command::Get(_),
+        ] if d.paths.len() == 2));
+        assert_eq!(run_script(json!([
+            {
+                "set": {
+                    "path": ".a.b",
+                    "data": 4,
+                    "missing-ok": true
+                }
+            },
+            {
+                "delete": ".c"
+            },
+        ]), Path::new("."), json!({
+            "a": {},
+            "c": 1
+        })), json!({
+            "a": {
+                "b": 4
+            }
+        }));
+    }
+
+    #[test]
+    fn script_step_errors() {
+        assert_eq!(
+            script_command("set", Supervalue::from(json!({
+                "path": ".a",
+                "data": 1,
+                "datta": 2,
+                "missing_ok": true,
+            })), Path::new(".")).err(),
+            Some("`set` has unknown arguments: datta, missing_ok".to_string())
+        );
+        assert_eq!(
+            script_command("set", Supervalue::from(json!({
+                "path": ".a"
+            })), Path::new(".")).err(),
+            Some("`set` is missing `data`".to_string())
+        );
+        assert_eq!(
+            script_command("sett", Supervalue::Null, Path::new(".")).err(),
+            Some("Unknown command `sett`".to_string())
+        );
+        assert_eq!(script_commands(Supervalue::from(json!(["infer-types", {
+            "set": 1
+        }])), Path::new(".")).err().map(|(index, _)| index), Some(1));
+    }
+
+    #[test]
+    fn script_references() {
+        let dir = std::env::temp_dir().join(format!("hoj-script-references-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("data.yaml"), "x: [1, 2]\n").unwrap();
+        std::env::set_var("HOJ_TEST_SCRIPT_REFERENCE", "from env");
+        assert_eq!(run_script(json!([
+            {
+                "set": {
+                    "path": ".file",
+                    "data": {
+                        "$file": "data.yaml"
+                    },
+                    "missing-ok": true
+                }
+            },
+            {
+                "set": {
+                    "path": ".env",
+                    "data": {
+                        "nested": {
+                            "$env": "HOJ_TEST_SCRIPT_REFERENCE"
+                        }
+                    },
+                    "missing-ok": true
+                }
+            },
+        ]), &dir, json!({})), json!({
+            "file": {
+                "x": [1, 2]
+            },
+            "env": {
+                "nested": "from env"
+            }
+        }));
+        assert!(script_command("set", Supervalue::from(json!({
+            "path": ".a",
+            "data": {
+                "$file": "missing.json"
+            }
+        })), &dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
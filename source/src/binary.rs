@@ -0,0 +1,270 @@
+use {
+    crate::{
+        supervalue::{
+            ConvertOptions,
+            Supervalue,
+            SupervalueMap,
+            SupervalueVec,
+            SupervalueVecType,
+        },
+        supervalue_number::NumberPrimitive,
+    },
+    std::str::FromStr,
+};
+
+fn from_cbor_value(value: ciborium::Value) -> Result<Supervalue, String> {
+    match value {
+        ciborium::Value::Integer(v) => return Ok(
+            Supervalue::Number(serde_json::Number::from_str(&i128::from(v).to_string()).unwrap().into()),
+        ),
+        ciborium::Value::Bytes(v) => return Ok(Supervalue::Bytes(v)),
+        ciborium::Value::Float(v) => return Ok(Supervalue::Number(v.into())),
+        ciborium::Value::Text(v) => return Ok(Supervalue::String(v)),
+        ciborium::Value::Bool(v) => return Ok(Supervalue::Bool(v)),
+        ciborium::Value::Null => return Ok(Supervalue::Null),
+        ciborium::Value::Tag(tag, v) => {
+            // Standard date/time string and full-date string tags
+            if let (0 | 1004, ciborium::Value::Text(text)) = (tag, &*v) {
+                if let Ok(d) = text.parse::<toml::value::Datetime>() {
+                    return Ok(Supervalue::Datetime(d.into()));
+                }
+            }
+            return Ok(Supervalue::Map(SupervalueMap::new_tagged(tag.to_string(), from_cbor_value(*v)?)));
+        },
+        ciborium::Value::Array(v) => return Ok(Supervalue::Vec(SupervalueVec {
+            type_: SupervalueVecType::Normal,
+            value: v.into_iter().map(from_cbor_value).collect::<Result<_, _>>()?,
+        })),
+        ciborium::Value::Map(v) => return Ok(
+            Supervalue::from_pairs(
+                v
+                    .into_iter()
+                    .map(|(k, v)| Ok((from_cbor_value(k)?, from_cbor_value(v)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+        ),
+        v => return Err(format!("Unsupported CBOR value: {:?}", v)),
+    }
+}
+
+fn to_cbor_value(value: &Supervalue, options: &ConvertOptions) -> Result<ciborium::Value, String> {
+    match value {
+        Supervalue::Map(map) => {
+            if let Some((tag, v)) = map.as_tagged() {
+                if let Ok(tag) = tag.parse::<u64>() {
+                    return Ok(ciborium::Value::Tag(tag, Box::new(to_cbor_value(v, options)?)));
+                }
+            }
+        },
+        Supervalue::Vec(vec) => {
+            if vec.type_ == SupervalueVecType::Normal {
+                return Ok(ciborium::Value::Array(vec.value.iter().map(|v| to_cbor_value(v, options)).collect::<Result<_, _>>()?));
+            }
+        },
+        Supervalue::Null => return Ok(ciborium::Value::Null),
+        Supervalue::Bool(v) => return Ok(ciborium::Value::Bool(*v)),
+        Supervalue::String(v) => return Ok(ciborium::Value::Text(v.clone())),
+        Supervalue::Number(v) => {
+            // CBOR integers range from -2^64 to 2^64-1
+            if let Some(i) = v.as_i128().and_then(|i| ciborium::value::Integer::try_from(i).ok()) {
+                return Ok(ciborium::Value::Integer(i));
+            }
+            match v.to_primitive("CBOR", true, options.lossy_numbers)? {
+                NumberPrimitive::U64(v) => return Ok(ciborium::Value::Integer(v.into())),
+                NumberPrimitive::I64(v) => return Ok(ciborium::Value::Integer(v.into())),
+                NumberPrimitive::F64(v) => return Ok(ciborium::Value::Float(v)),
+            }
+        },
+        Supervalue::Datetime(v) => {
+            let text = ciborium::Value::Text(v.to_string());
+            if v.instant().is_some() {
+                // Standard date/time string
+                return Ok(ciborium::Value::Tag(0, Box::new(text)));
+            } else if v.0.time.is_none() {
+                // Full-date string
+                return Ok(ciborium::Value::Tag(1004, Box::new(text)));
+            } else {
+                return Ok(text);
+            }
+        },
+        Supervalue::Bytes(v) => return Ok(ciborium::Value::Bytes(v.clone())),
+    }
+    let Some(pairs) = value.map_pairs() else {
+        return Err(format!("Invalid complex key map"));
+    };
+    let mut out = vec![];
+    for (k, v) in pairs {
+        out.push((to_cbor_value(&k, options)?, to_cbor_value(v, options)?));
+    }
+    return Ok(ciborium::Value::Map(out));
+}
+
+/// Parse CBOR. Byte strings become `Supervalue::Bytes`, date/time strings (tags 0
+/// and 1004) become datetimes, other tagged values become tag maps with the tag
+/// number as a string, and maps with non-string keys use the complex key
+/// representation.
+pub fn from_cbor_slice(data: &[u8]) -> Result<Supervalue, String> {
+    let value = ciborium::from_reader::<ciborium::Value, _>(data).map_err(|e| e.to_string())?;
+    return from_cbor_value(value);
+}
+
+/// Write data as CBOR, the reverse of `from_cbor_slice`. Datetimes with an
+/// offset are written as standard date/time strings (tag 0), dates as full-date
+/// strings (tag 1004), and others as plain strings.
+pub fn to_cbor_vec(value: &Supervalue, options: &ConvertOptions) -> Result<Vec<u8>, String> {
+    let value = to_cbor_value(value, options)?;
+    let mut out = vec![];
+    ciborium::into_writer(&value, &mut out).map_err(|e| e.to_string())?;
+    return Ok(out);
+}
+
+fn from_msgpack_value(value: rmpv::Value) -> Supervalue {
+    match value {
+        rmpv::Value::Nil => return Supervalue::Null,
+        rmpv::Value::Boolean(v) => return Supervalue::Bool(v),
+        rmpv::Value::Integer(v) => {
+            if let Some(i) = v.as_u64() {
+                return Supervalue::Number(i.into());
+            } else {
+                return Supervalue::Number(v.as_i64().unwrap().into());
+            }
+        },
+        rmpv::Value::F32(v) => return Supervalue::Number((v as f64).into()),
+        rmpv::Value::F64(v) => return Supervalue::Number(v.into()),
+        rmpv::Value::String(v) => {
+            if v.is_str() {
+                return Supervalue::String(v.into_str().unwrap());
+            } else {
+                return Supervalue::Bytes(v.into_bytes());
+            }
+        },
+        rmpv::Value::Binary(v) => return Supervalue::Bytes(v),
+        rmpv::Value::Array(v) => return Supervalue::Vec(SupervalueVec {
+            type_: SupervalueVecType::Normal,
+            value: v.into_iter().map(from_msgpack_value).collect(),
+        }),
+        rmpv::Value::Map(v) => return Supervalue::from_pairs(
+            v.into_iter().map(|(k, v)| (from_msgpack_value(k), from_msgpack_value(v))).collect(),
+        ),
+        rmpv::Value::Ext(tag, v) => return Supervalue::Map(SupervalueMap::new_tagged(tag.to_string(), Supervalue::Bytes(v))),
+    }
+}
+
+fn to_msgpack_value(value: &Supervalue, options: &ConvertOptions) -> Result<rmpv::Value, String> {
+    match value {
+        Supervalue::Map(map) => {
+            if let Some((tag, Supervalue::Bytes(v))) = map.as_tagged() {
+                if let Ok(tag) = tag.parse::<i8>() {
+                    return Ok(rmpv::Value::Ext(tag, v.clone()));
+                }
+            }
+        },
+        Supervalue::Vec(vec) => {
+            if vec.type_ == SupervalueVecType::Normal {
+                return Ok(rmpv::Value::Array(vec.value.iter().map(|v| to_msgpack_value(v, options)).collect::<Result<_, _>>()?));
+            }
+        },
+        Supervalue::Null => return Ok(rmpv::Value::Nil),
+        Supervalue::Bool(v) => return Ok(rmpv::Value::Boolean(*v)),
+        Supervalue::String(v) => return Ok(rmpv::Value::from(v.as_str())),
+        Supervalue::Number(v) => match v.to_primitive("MessagePack", true, options.lossy_numbers)? {
+            NumberPrimitive::U64(v) => return Ok(rmpv::Value::from(v)),
+            NumberPrimitive::I64(v) => return Ok(rmpv::Value::from(v)),
+            NumberPrimitive::F64(v) => return Ok(rmpv::Value::F64(v)),
+        },
+        Supervalue::Datetime(v) => return Ok(rmpv::Value::from(v.to_string())),
+        Supervalue::Bytes(v) => return Ok(rmpv::Value::Binary(v.clone())),
+    }
+    let Some(pairs) = value.map_pairs() else {
+        return Err(format!("Invalid complex key map"));
+    };
+    let mut out = vec![];
+    for (k, v) in pairs {
+        out.push((to_msgpack_value(&k, options)?, to_msgpack_value(v, options)?));
+    }
+    return Ok(rmpv::Value::Map(out));
+}
+
+/// Parse MessagePack. Binary (and non-utf-8 strings) become `Supervalue::Bytes`,
+/// extension values become tag maps with the extension type as a string, and maps
+/// with non-string keys use the complex key representation.
+pub fn from_msgpack_slice(data: &[u8]) -> Result<Supervalue, String> {
+    let value = rmpv::decode::read_value(&mut &data[..]).map_err(|e| e.to_string())?;
+    return Ok(from_msgpack_value(value));
+}
+
+/// Write data as MessagePack, the reverse of `from_msgpack_slice`.
+pub fn to_msgpack_vec(value: &Supervalue, options: &ConvertOptions) -> Result<Vec<u8>, String> {
+    let value = to_msgpack_value(value, options)?;
+    let mut out = vec![];
+    rmpv::encode::write_value(&mut out, &value).map_err(|e| e.to_string())?;
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_cbor_slice,
+            from_msgpack_slice,
+            to_cbor_vec,
+            to_msgpack_vec,
+        },
+        crate::{
+            supervalue::{
+                ConvertOptions,
+                Supervalue,
+            },
+            supervalue_datetime::SupervalueDatetime,
+        },
+        serde_json::json,
+    };
+
+    fn source() -> Supervalue {
+        let Supervalue::Map(mut source) = Supervalue::from(json!({
+            "a": [1, -2, 1.5, "x", null, true],
+            "big": 18446744073709551615u64,
+        })) else {
+            unreachable!();
+        };
+        source.value.insert("bin".to_string(), Supervalue::Bytes(vec![0, 1, 255]));
+        return Supervalue::Map(source);
+    }
+
+    #[test]
+    fn cbor() {
+        let source = source();
+        assert_eq!(from_cbor_slice(&to_cbor_vec(&source, &ConvertOptions::default()).unwrap()).unwrap(), source);
+        let Supervalue::Map(mut source) = source else {
+            unreachable!();
+        };
+        for (k, v) in [("date", "2001-12-14"), ("instant", "2001-12-14T21:59:43-05:00")] {
+            source.value.insert(k.to_string(), Supervalue::Datetime(SupervalueDatetime::parse(v).unwrap()));
+        }
+        let source = Supervalue::Map(source);
+        assert_eq!(from_cbor_slice(&to_cbor_vec(&source, &ConvertOptions::default()).unwrap()).unwrap(), source);
+    }
+
+    #[test]
+    fn cbor_tags() {
+        let mut data = vec![];
+        ciborium::into_writer(
+            &ciborium::Value::Map(
+                vec![
+                    (
+                        ciborium::Value::Text("a".to_string()),
+                        ciborium::Value::Tag(32, Box::new(ciborium::Value::Text("https://x".to_string()))),
+                    )
+                ],
+            ),
+            &mut data,
+        ).unwrap();
+        assert_eq!(to_cbor_vec(&from_cbor_slice(&data).unwrap(), &ConvertOptions::default()).unwrap(), data);
+    }
+
+    #[test]
+    fn msgpack() {
+        let source = source();
+        assert_eq!(from_msgpack_slice(&to_msgpack_vec(&source, &ConvertOptions::default()).unwrap()).unwrap(), source);
+    }
+}
@@ -0,0 +1,38 @@
+use {
+    similar::TextDiff,
+};
+
+/// Compare the original contents of a file with new contents, returning `None` if
+/// they're the same, otherwise a unified diff (or a note if either isn't text).
+pub fn diff_bytes(name: &str, old: &[u8], new: &[u8]) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return Some(format!("Binary file {} would change\n", name));
+    };
+    return Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&format!("a/{}", name), &format!("b/{}", name))
+            .to_string(),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::diff_bytes,
+    };
+
+    #[test]
+    fn diff() {
+        assert_eq!(diff_bytes("x.json", b"{}\n", b"{}\n"), None);
+        assert_eq!(
+            diff_bytes("x.json", b"{\n  \"a\": 1\n}\n", b"{\n  \"a\": 2\n}\n").unwrap(),
+            "--- a/x.json\n+++ b/x.json\n@@ -1,3 +1,3 @@\n {\n-  \"a\": 1\n+  \"a\": 2\n }\n"
+        );
+        assert_eq!(diff_bytes("x.cbor", &[0xff], &[0xfe]).unwrap(), "Binary file x.cbor would change\n");
+    }
+}
@@ -0,0 +1,796 @@
+use {
+    crate::supervalue::Supervalue,
+    std::collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "const",
+    "continue",
+    "crate",
+    "dyn",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "fn",
+    "for",
+    "if",
+    "impl",
+    "in",
+    "let",
+    "loop",
+    "match",
+    "mod",
+    "move",
+    "mut",
+    "pub",
+    "ref",
+    "return",
+    "self",
+    "Self",
+    "static",
+    "struct",
+    "super",
+    "trait",
+    "true",
+    "type",
+    "unsafe",
+    "use",
+    "where",
+    "while",
+    "abstract",
+    "become",
+    "box",
+    "do",
+    "final",
+    "macro",
+    "override",
+    "priv",
+    "try",
+    "typeof",
+    "unsized",
+    "virtual",
+    "yield",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum RustType {
+    Any,
+    Unit,
+    Bool,
+    I64,
+    U64,
+    F64,
+    String,
+    Vec(Box<RustType>),
+    Map(Box<RustType>),
+    Option(Box<RustType>),
+    Box(Box<RustType>),
+    Named(String),
+}
+
+impl RustType {
+    fn render(&self) -> String {
+        match self {
+            RustType::Any => return "serde_json::Value".to_string(),
+            RustType::Unit => return "()".to_string(),
+            RustType::Bool => return "bool".to_string(),
+            RustType::I64 => return "i64".to_string(),
+            RustType::U64 => return "u64".to_string(),
+            RustType::F64 => return "f64".to_string(),
+            RustType::String => return "String".to_string(),
+            RustType::Vec(t) => return format!("Vec<{}>", t.render()),
+            RustType::Map(t) => return format!("std::collections::HashMap<String, {}>", t.render()),
+            RustType::Option(t) => return format!("Option<{}>", t.render()),
+            RustType::Box(t) => return format!("Box<{}>", t.render()),
+            RustType::Named(n) => return n.clone(),
+        }
+    }
+}
+
+struct Field {
+    key: String,
+    description: Option<String>,
+    type_: RustType,
+    required: bool,
+    default: Option<serde_json::Value>,
+}
+
+enum Def {
+    Struct {
+        description: Option<String>,
+        fields: Vec<Field>,
+    },
+    Enum {
+        description: Option<String>,
+        variants: Vec<String>,
+    },
+}
+
+#[derive(Default)]
+struct Defs {
+    order: Vec<String>,
+    defs: BTreeMap<String, Def>,
+    reserved: BTreeSet<String>,
+}
+
+impl Defs {
+    /// Reserve a unique type name based on `base`.
+    fn reserve(&mut self, base: &str) -> String {
+        let base = type_ident(base);
+        let mut name = base.clone();
+        let mut i = 2;
+        while self.reserved.contains(&name) {
+            name = format!("{}{}", base, i);
+            i += 1;
+        }
+        self.reserved.insert(name.clone());
+        return name;
+    }
+
+    fn add(&mut self, name: String, def: Def) {
+        self.order.push(name.clone());
+        self.defs.insert(name, def);
+    }
+
+    fn render(&self) -> String {
+        let mut out = vec![];
+        for name in &self.order {
+            let mut text = String::new();
+            match &self.defs[name] {
+                Def::Struct { description, fields } => {
+                    render_doc(&mut text, "", description);
+                    text.push_str("#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]\n");
+                    text.push_str(&format!("pub struct {} {{\n", name));
+                    let mut seen = BTreeSet::new();
+                    let mut default_fns = vec![];
+                    for field in fields {
+                        render_doc(&mut text, "    ", &field.description);
+                        let mut ident = field_ident(&field.key);
+                        let base = ident.clone();
+                        let mut i = 2;
+                        while !seen.insert(ident.clone()) {
+                            ident = format!("{}{}", base, i);
+                            i += 1;
+                        }
+                        let mut attrs = vec![];
+                        if ident != field.key {
+                            attrs.push(format!("rename = {}", serde_json::to_string(&field.key).unwrap()));
+                        }
+                        let type_;
+                        if let Some(default) = &field.default {
+                            // Missing values get the default, required or not
+                            let fn_name = format!("default_{}_{}", field_ident(name), ident);
+                            attrs.push(format!("default = \"{}\"", fn_name));
+                            default_fns.push(
+                                format!(
+                                    "fn {}() -> {} {{\n    return {};\n}}\n",
+                                    fn_name,
+                                    field.type_.render(),
+                                    render_default(&field.type_, default)
+                                ),
+                            );
+                            type_ = field.type_.clone();
+                        } else if field.required {
+                            type_ = field.type_.clone();
+                        } else {
+                            attrs.push("default".to_string());
+                            attrs.push("skip_serializing_if = \"Option::is_none\"".to_string());
+                            type_ = match &field.type_ {
+                                RustType::Option(_) => field.type_.clone(),
+                                t => RustType::Option(Box::new(t.clone())),
+                            };
+                        }
+                        if !attrs.is_empty() {
+                            text.push_str(&format!("    #[serde({})]\n", attrs.join(", ")));
+                        }
+                        text.push_str(&format!("    pub {}: {},\n", ident, type_.render()));
+                    }
+                    text.push_str("}\n");
+                    for default_fn in default_fns {
+                        text.push('\n');
+                        text.push_str(&default_fn);
+                    }
+                },
+                Def::Enum { description, variants } => {
+                    render_doc(&mut text, "", description);
+                    text.push_str(
+                        "#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]\n",
+                    );
+                    text.push_str(&format!("pub enum {} {{\n", name));
+                    let mut seen = BTreeSet::new();
+                    for variant in variants {
+                        let mut ident = type_ident(variant);
+                        let base = ident.clone();
+                        let mut i = 2;
+                        while !seen.insert(ident.clone()) {
+                            ident = format!("{}{}", base, i);
+                            i += 1;
+                        }
+                        text.push_str(
+                            &format!("    #[serde(rename = {})]\n", serde_json::to_string(variant).unwrap()),
+                        );
+                        text.push_str(&format!("    {},\n", ident));
+                    }
+                    text.push_str("}\n");
+                },
+            }
+            out.push(text);
+        }
+        return out.join("\n");
+    }
+}
+
+/// Rust expression producing the schema `default` value for a field of type `t`.
+fn render_default(t: &RustType, value: &serde_json::Value) -> String {
+    match (t, value) {
+        (RustType::Bool, serde_json::Value::Bool(b)) => return b.to_string(),
+        (RustType::I64, serde_json::Value::Number(n)) if n.is_i64() => return n.to_string(),
+        (RustType::U64, serde_json::Value::Number(n)) if n.is_u64() => return n.to_string(),
+        (RustType::F64, serde_json::Value::Number(n)) => return format!("{:?}", n.as_f64().unwrap()),
+        (RustType::String, serde_json::Value::String(s)) => {
+            return format!("{}.to_string()", serde_json::to_string(s).unwrap());
+        },
+        _ => return format!(
+            "serde_json::from_value(serde_json::json!({})).unwrap()",
+            serde_json::to_string(value).unwrap()
+        ),
+    }
+}
+
+fn render_doc(out: &mut String, indent: &str, description: &Option<String>) {
+    let Some(description) = description else {
+        return;
+    };
+    for line in description.lines() {
+        if line.is_empty() {
+            out.push_str(&format!("{}///\n", indent));
+        } else {
+            out.push_str(&format!("{}/// {}\n", indent, line));
+        }
+    }
+}
+
+fn words(s: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                out.push(current.split_off(0));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower && !current.is_empty() {
+            out.push(current.split_off(0));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    return out;
+}
+
+/// PascalCase identifier for a type or enum variant.
+fn type_ident(s: &str) -> String {
+    let mut out = String::new();
+    for word in words(s) {
+        let mut chars = word.chars();
+        let first = chars.next().unwrap();
+        out.push(first.to_ascii_uppercase());
+        out.extend(chars.map(|c| c.to_ascii_lowercase()));
+    }
+    if out.is_empty() {
+        out = "Value".to_string();
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out = format!("V{}", out);
+    }
+    if RUST_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    }
+    return out;
+}
+
+/// snake_case identifier for a field.
+fn field_ident(s: &str) -> String {
+    let mut out = words(s).iter().map(|w| w.to_ascii_lowercase()).collect::<Vec<_>>().join("_");
+    if out.is_empty() {
+        out = "field".to_string();
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out = format!("f{}", out);
+    }
+    if RUST_KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    }
+    return out;
+}
+
+struct SchemaContext<'a> {
+    root: &'a serde_json::Value,
+    defs: Defs,
+    /// Types already resolved for `$ref` targets
+    refs: BTreeMap<String, RustType>,
+    /// `$ref` targets currently being resolved, and the name reserved for each
+    resolving: BTreeMap<String, String>,
+}
+
+impl<'a> SchemaContext<'a> {
+    fn resolve_ref(&mut self, r: &str) -> Result<RustType, String> {
+        if let Some(t) = self.refs.get(r) {
+            return Ok(t.clone());
+        }
+        if let Some(name) = self.resolving.get(r) {
+            // Recursive reference, needs indirection to have a finite size
+            return Ok(RustType::Box(Box::new(RustType::Named(name.clone()))));
+        }
+        let Some(pointer) = r.strip_prefix("#") else {
+            return Err(format!("Only local `$ref`s (starting with `#`) are supported, got [{}]", r));
+        };
+        if pointer.is_empty() {
+            return Err(format!("Recursive `$ref` to the schema root isn't supported"));
+        }
+        let root = self.root;
+        let Some(target) = root.pointer(pointer) else {
+            return Err(format!("Schema `$ref` [{}] doesn't point to anything in the schema", r));
+        };
+        let base = target.get("title").and_then(|t| t.as_str()).unwrap_or(pointer.rsplit("/").next().unwrap());
+        let name = self.defs.reserve(base);
+
+        // Register before recursing so self-referential definitions terminate
+        self.resolving.insert(r.to_string(), name.clone());
+        let t = self.schema_type(&name, target)?;
+        self.resolving.remove(r);
+        self.refs.insert(r.to_string(), t.clone());
+        return Ok(t);
+    }
+
+    /// Determine the type for the schema, defining any necessary structs/enums.
+    /// `name` is the reserved name to use if a definition is created.
+    fn schema_type(&mut self, name: &str, schema: &serde_json::Value) -> Result<RustType, String> {
+        let serde_json::Value::Object(schema) = schema else {
+            // Boolean schemas
+            return Ok(RustType::Any);
+        };
+        if let Some(serde_json::Value::String(r)) = schema.get("$ref") {
+            return self.resolve_ref(r);
+        }
+        let description = schema.get("description").and_then(|d| d.as_str()).map(|d| d.to_string());
+        let mut nullable = false;
+        let type_ = match schema.get("type") {
+            Some(serde_json::Value::String(t)) => Some(t.as_str()),
+            Some(serde_json::Value::Array(types)) => {
+                let mut non_null = vec![];
+                for t in types {
+                    match t.as_str() {
+                        Some("null") => nullable = true,
+                        Some(t) => non_null.push(t),
+                        None => return Err(format!("Schema `type` array contains a non-string element")),
+                    }
+                }
+                if non_null.len() == 1 {
+                    Some(non_null[0])
+                } else if non_null.is_empty() {
+                    Some("null")
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+        let out = if let Some(serde_json::Value::Array(variants)) = schema.get("enum") {
+            if variants.iter().all(|v| v.is_string()) {
+                self.defs.add(name.to_string(), Def::Enum {
+                    description: description,
+                    variants: variants.iter().map(|v| v.as_str().unwrap().to_string()).collect(),
+                });
+                RustType::Named(name.to_string())
+            } else {
+                RustType::Any
+            }
+        } else {
+            match type_ {
+                Some("null") => RustType::Unit,
+                Some("boolean") => RustType::Bool,
+                Some("integer") => {
+                    if schema.get("minimum").and_then(|m| m.as_f64()).map(|m| m >= 0.).unwrap_or(false) {
+                        RustType::U64
+                    } else {
+                        RustType::I64
+                    }
+                },
+                Some("number") => RustType::F64,
+                Some("string") => RustType::String,
+                Some("array") => match schema.get("items") {
+                    Some(items) => {
+                        let item_name = self.defs.reserve(&format!("{}Item", name));
+                        RustType::Vec(Box::new(self.schema_type(&item_name, items)?))
+                    },
+                    None => RustType::Vec(Box::new(RustType::Any)),
+                },
+                Some("object") | None if schema.contains_key("properties") => {
+                    let required =
+                        schema
+                            .get("required")
+                            .and_then(|r| r.as_array())
+                            .map(
+                                |r| r.iter().filter_map(|k| k.as_str()).map(|k| k.to_string()).collect::<BTreeSet<_>>(),
+                            )
+                            .unwrap_or_default();
+                    let mut fields = vec![];
+                    for (key, prop) in schema.get("properties").unwrap().as_object().into_iter().flatten() {
+                        let prop_name = self.defs.reserve(key);
+                        let prop_type = self.schema_type(&prop_name, prop)?;
+                        fields.push(Field {
+                            key: key.clone(),
+                            description: prop
+                                .get("description")
+                                .and_then(|d| d.as_str())
+                                .map(|d| d.to_string()),
+                            type_: prop_type,
+                            required: required.contains(key),
+                            default: prop.get("default").cloned(),
+                        });
+                    }
+                    self.defs.add(name.to_string(), Def::Struct {
+                        description: description,
+                        fields: fields,
+                    });
+                    RustType::Named(name.to_string())
+                },
+                Some("object") => match schema.get("additionalProperties") {
+                    Some(serde_json::Value::Object(_)) => {
+                        let value_name = self.defs.reserve(&format!("{}Value", name));
+                        RustType::Map(
+                            Box::new(self.schema_type(&value_name, schema.get("additionalProperties").unwrap())?),
+                        )
+                    },
+                    _ => RustType::Map(Box::new(RustType::Any)),
+                },
+                _ => RustType::Any,
+            }
+        };
+        if nullable && out != RustType::Unit {
+            return Ok(RustType::Option(Box::new(out)));
+        }
+        return Ok(out);
+    }
+}
+
+/// Generate Rust type definitions (with serde attributes) from a JSON Schema.
+/// Only local `$ref`s are supported; `oneOf`/`anyOf` and other constructs that
+/// don't map cleanly to a Rust type become `serde_json::Value`.
+pub fn codegen_schema(schema: &serde_json::Value, root_name: &str) -> Result<String, String> {
+    let mut context = SchemaContext {
+        root: schema,
+        defs: Defs::default(),
+        refs: BTreeMap::new(),
+        resolving: BTreeMap::new(),
+    };
+    let name = context.defs.reserve(root_name);
+    let t = context.schema_type(&name, schema)?;
+    if t != RustType::Named(name.clone()) {
+        let mut out = context.defs.render();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("pub type {} = {};\n", name, t.render()));
+        return Ok(out);
+    }
+    return Ok(context.defs.render());
+}
+
+fn merge_inferred(defs: &mut Defs, a: RustType, b: RustType) -> RustType {
+    match (a, b) {
+        (a, b) if a == b => return a,
+        (RustType::Unit, b) | (b, RustType::Unit) => match b {
+            RustType::Option(_) => return b,
+            b => return RustType::Option(Box::new(b)),
+        },
+        (RustType::Option(a), b) | (b, RustType::Option(a)) => {
+            let inner = merge_inferred(defs, *a, b);
+            match inner {
+                RustType::Option(_) => return inner,
+                inner => return RustType::Option(Box::new(inner)),
+            }
+        },
+        (RustType::I64, RustType::U64) | (RustType::U64, RustType::I64) => return RustType::I64,
+        (RustType::I64 | RustType::U64, RustType::F64) | (RustType::F64, RustType::I64 | RustType::U64) => {
+            return RustType::F64;
+        },
+        (RustType::Vec(a), RustType::Vec(b)) => return RustType::Vec(Box::new(merge_inferred(defs, *a, *b))),
+        (RustType::Named(a), RustType::Named(b)) => {
+            let (Some(Def::Struct { .. }), Some(Def::Struct { .. })) = (defs.defs.get(&a), defs.defs.get(&b)) else {
+                return RustType::Any;
+            };
+            defs.order.retain(|n| *n != b);
+            let Some(Def::Struct { fields: b_fields, .. }) = defs.defs.remove(&b) else {
+                unreachable!();
+            };
+            let Some(Def::Struct { fields: mut a_fields, description }) = defs.defs.remove(&a) else {
+                unreachable!();
+            };
+            let b_keys = b_fields.iter().map(|f| f.key.clone()).collect::<BTreeSet<_>>();
+            for field in &mut a_fields {
+                if !b_keys.contains(&field.key) {
+                    field.required = false;
+                }
+            }
+            for b_field in b_fields {
+                if let Some(a_field) = a_fields.iter_mut().find(|f| f.key == b_field.key) {
+                    let a_type = std::mem::replace(&mut a_field.type_, RustType::Any);
+                    a_field.type_ = merge_inferred(defs, a_type, b_field.type_);
+                    a_field.required = a_field.required && b_field.required;
+                } else {
+                    a_fields.push(Field {
+                        required: false,
+                        ..b_field
+                    });
+                }
+            }
+            defs.defs.insert(a.clone(), Def::Struct {
+                description: description,
+                fields: a_fields,
+            });
+            return RustType::Named(a);
+        },
+        _ => return RustType::Any,
+    }
+}
+
+fn infer_type(defs: &mut Defs, name: &str, value: &Supervalue) -> RustType {
+    match value {
+        Supervalue::Map(m) => {
+            let mut keys = m.value.keys().collect::<Vec<_>>();
+            keys.sort();
+            let mut fields = vec![];
+            for key in keys {
+                let field_name = defs.reserve(key);
+                let t = infer_type(defs, &field_name, &m.value[key]);
+                fields.push(Field {
+                    key: key.clone(),
+                    description: None,
+                    type_: t,
+                    required: true,
+                    default: None,
+                });
+            }
+            defs.add(name.to_string(), Def::Struct {
+                description: None,
+                fields: fields,
+            });
+            return RustType::Named(name.to_string());
+        },
+        Supervalue::Vec(v) => {
+            let item_name = format!("{}Item", name);
+            let mut out: Option<RustType> = None;
+            for e in &v.value {
+                let item_name = defs.reserve(&item_name);
+                let t = infer_type(defs, &item_name, e);
+                out = Some(match out {
+                    Some(prev) => merge_inferred(defs, prev, t),
+                    None => t,
+                });
+            }
+            return RustType::Vec(Box::new(out.unwrap_or(RustType::Any)));
+        },
+        Supervalue::Null => return RustType::Unit,
+        Supervalue::Bool(_) => return RustType::Bool,
+        Supervalue::String(_) => return RustType::String,
+        Supervalue::Number(n) => {
+            if n.as_i64().is_some() {
+                return RustType::I64;
+            } else if n.as_u64().is_some() {
+                return RustType::U64;
+            } else {
+                return RustType::F64;
+            }
+        },
+        Supervalue::Datetime(_) => return RustType::String,
+        Supervalue::Bytes(_) => return RustType::String,
+    }
+}
+
+/// Generate Rust type definitions (with serde attributes) matching the shape of a
+/// document. Fields missing from some elements of an array of objects become
+/// `Option`s, and values that only appear as `null` become
+/// `Option<serde_json::Value>`.
+pub fn codegen_value(value: &Supervalue, root_name: &str) -> String {
+    let mut defs = Defs::default();
+    let name = defs.reserve(root_name);
+    let t = infer_type(&mut defs, &name, value);
+    let t = match t {
+        RustType::Unit => RustType::Option(Box::new(RustType::Any)),
+        t => t,
+    };
+    for def in defs.defs.values_mut() {
+        let Def::Struct { fields, .. } = def else {
+            continue;
+        };
+        for field in fields {
+            match &field.type_ {
+                RustType::Unit => field.type_ = RustType::Option(Box::new(RustType::Any)),
+                RustType::Option(inner) if **inner == RustType::Unit => {
+                    field.type_ = RustType::Option(Box::new(RustType::Any))
+                },
+                _ => { },
+            }
+        }
+    }
+    if t != RustType::Named(name.clone()) {
+        let mut out = defs.render();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("pub type {} = {};\n", name, t.render()));
+        return out;
+    }
+    return defs.render();
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            codegen_schema,
+            codegen_value,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn schema() {
+        let out = codegen_schema(&json!({
+            "type": "object",
+            "properties": {
+                "listenAddr": {
+                    "type": "string",
+                    "description": "Address to bind"
+                },
+                "mode": {
+                    "enum": ["fast", "slow"],
+                    "default": "fast"
+                },
+                "retries": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "default": 3
+                },
+                "tags": {
+                    "type": "array",
+                    "items": {
+                        "type": "string"
+                    }
+                },
+                "timeout": {
+                    "type": "number",
+                    "default": 1.5
+                }
+            },
+            "required": ["listenAddr", "timeout"]
+        }), "config").unwrap();
+        assert_eq!(
+            out,
+            r#"#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Mode {
+    #[serde(rename = "fast")]
+    Fast,
+    #[serde(rename = "slow")]
+    Slow,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Config {
+    /// Address to bind
+    #[serde(rename = "listenAddr")]
+    pub listen_addr: String,
+    #[serde(default = "default_config_mode")]
+    pub mode: Mode,
+    #[serde(default = "default_config_retries")]
+    pub retries: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default = "default_config_timeout")]
+    pub timeout: f64,
+}
+
+fn default_config_mode() -> Mode {
+    return serde_json::from_value(serde_json::json!("fast")).unwrap();
+}
+
+fn default_config_retries() -> u64 {
+    return 3;
+}
+
+fn default_config_timeout() -> f64 {
+    return 1.5;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn schema_recursive_and_colliding() {
+        let out = codegen_schema(&json!({
+            "$ref": "#/$defs/node",
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": {
+                        "a-b": {
+                            "type": "string"
+                        },
+                        "a_b": {
+                            "type": "string"
+                        },
+                        "next": {
+                            "$ref": "#/$defs/node"
+                        }
+                    },
+                    "required": ["a-b", "a_b"]
+                }
+            }
+        }), "root").unwrap();
+        assert_eq!(
+            out,
+            r#"#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Node {
+    #[serde(rename = "a-b")]
+    pub a_b: String,
+    #[serde(rename = "a_b")]
+    pub a_b2: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<Box<Node>>,
+}
+
+pub type Root = Node;
+"#
+        );
+    }
+
+    #[test]
+    fn inferred() {
+        let out = codegen_value(&Supervalue::from(json!({
+            "type": "x",
+            "servers": [
+                {
+                    "port": 80,
+                },
+                {
+                    "port": 443,
+                    "host": "example.com"
+                }
+            ]
+        })), "Root");
+        assert_eq!(
+            out,
+            r#"#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ServersItem {
+    pub port: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Root {
+    pub servers: Vec<ServersItem>,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+"#
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use {
     crate::{
+        error::Error,
         supervalue::Supervalue,
         supervalue_path::DataPath,
         utils::{
@@ -11,7 +12,7 @@ use {
     },
 };
 
-pub fn delete(source: &mut Supervalue, path: &DataPath, missing_ok: bool) -> Result<(), String> {
+pub fn delete(source: &mut Supervalue, path: &DataPath, missing_ok: bool) -> Result<(), Error> {
     at_path(
         //. .
         &path,
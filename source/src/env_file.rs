@@ -0,0 +1,200 @@
+use {
+    crate::supervalue::{
+        Supervalue,
+        SupervalueMap,
+        SupervalueMapType,
+    },
+    base64::prelude::{
+        BASE64_STANDARD,
+        Engine,
+    },
+};
+
+fn parse_double_quoted(line_number: usize, chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(format!("Line {}: unterminated double quoted value", line_number)),
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some(c) => out.push(c),
+                None => return Err(format!("Line {}: unterminated escape", line_number)),
+            },
+            Some(c) => out.push(c),
+        }
+    }
+}
+
+/// Only whitespace and a `#` comment may follow a closing quote.
+fn check_after_quote(line_number: usize, rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with("#") {
+        return Err(format!("Line {}: unexpected text after closing quote [{}]", line_number, rest));
+    }
+    return Ok(());
+}
+
+/// Parse a `.env` file into a map of strings. Supports `#` comments, an optional
+/// `export ` prefix, and single (literal) or double (with backslash escapes)
+/// quoted values. Unquoted values end at an inline ` #` comment and are trimmed,
+/// and quoted values may only be followed by a comment.
+pub fn from_env_str(text: &str) -> Result<Supervalue, String> {
+    let mut out = SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: Default::default(),
+    };
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once("=") else {
+            return Err(format!("Line {}: expected KEY=VALUE", line_number));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("Line {}: invalid key [{}]", line_number, key));
+        }
+        let value = value.trim_start();
+        let value = if let Some(rest) = value.strip_prefix("\"") {
+            // Double quoted values may span multiple lines
+            let mut text = rest.to_string();
+            let mut parsed;
+            loop {
+                let mut chars = text.chars().peekable();
+                parsed = parse_double_quoted(line_number, &mut chars);
+                if parsed.is_ok() {
+                    check_after_quote(line_number, &chars.collect::<String>())?;
+                    break;
+                }
+                let Some((_, next)) = lines.next() else {
+                    break;
+                };
+                text.push('\n');
+                text.push_str(next);
+            }
+            parsed?
+        } else if let Some(rest) = value.strip_prefix("'") {
+            let Some((inner, after)) = rest.split_once("'") else {
+                return Err(format!("Line {}: unterminated single quoted value", line_number));
+            };
+            check_after_quote(line_number, after)?;
+            inner.to_string()
+        } else {
+            match value.split_once(" #") {
+                Some((v, _)) => v.trim().to_string(),
+                None => value.trim().to_string(),
+            }
+        };
+        out.value.insert(key.to_string(), Supervalue::String(value));
+    }
+    return Ok(Supervalue::Map(out));
+}
+
+/// The text of a primitive value, or `None` if it's a map or array.
+pub(crate) fn primitive_text(value: &Supervalue) -> Option<String> {
+    match value {
+        Supervalue::Map(_) | Supervalue::Vec(_) => return None,
+        Supervalue::Null => return Some(String::new()),
+        Supervalue::Bool(v) => return Some(v.to_string()),
+        Supervalue::String(v) => return Some(v.clone()),
+        Supervalue::Number(v) => return Some(v.to_string()),
+        Supervalue::Datetime(v) => return Some(v.to_string()),
+        Supervalue::Bytes(v) => return Some(BASE64_STANDARD.encode(v)),
+    }
+}
+
+/// Write a map of primitives as a `.env` file. Values other than simple words
+/// are double quoted.
+pub fn to_env_string(value: &Supervalue) -> Result<String, String> {
+    let Supervalue::Map(map) = value else {
+        return Err(format!(".env output requires a map of primitive values, but the data isn't a map"));
+    };
+    let mut keys = map.value.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut out = String::new();
+    for k in keys {
+        if k.is_empty() || k.contains(|c: char| c.is_whitespace() || c == '=') {
+            return Err(format!("Key [{}] can't be written as a .env key", k));
+        }
+        let Some(v) = primitive_text(&map.value[k]) else {
+            return Err(
+                format!(
+                    "Value at [{}] is a map or array, but .env files can only contain primitive values (the data is too deep)",
+                    k
+                ),
+            );
+        };
+        out.push_str(k);
+        out.push('=');
+        if v.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:,@+".contains(c)) {
+            out.push_str(&v);
+        } else {
+            out.push('"');
+            for c in v.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        out.push('\n');
+    }
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_env_str,
+            to_env_string,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn round_trip() {
+        let source =
+            from_env_str(
+                "# comment\nexport A=1 # inline\nB = 'x # y' # inline\nC=\"line\\n\\\"q\\\"\"\nD=\"multi\nline\"\nE=\n",
+            ).unwrap();
+        assert_eq!(source, Supervalue::from(json!({
+            "A": "1",
+            "B": "x # y",
+            "C": "line\n\"q\"",
+            "D": "multi\nline",
+            "E": "",
+        })));
+        let text = to_env_string(&source).unwrap();
+        assert_eq!(text, "A=1\nB=\"x # y\"\nC=\"line\\n\\\"q\\\"\"\nD=\"multi\\nline\"\nE=\n");
+        assert_eq!(from_env_str(&text).unwrap(), source);
+        assert!(to_env_string(&Supervalue::from(json!({
+            "A": {
+                "b": 1
+            }
+        }))).is_err());
+    }
+
+    #[test]
+    fn text_after_quote() {
+        assert_eq!(from_env_str("A=\"x\" junk\n").unwrap_err(), "Line 1: unexpected text after closing quote [junk]");
+        assert_eq!(from_env_str("A='x'junk\n").unwrap_err(), "Line 1: unexpected text after closing quote [junk]");
+        assert_eq!(from_env_str("A=\"x\"  \nB='y'\t#c\n").unwrap(), Supervalue::from(json!({
+            "A": "x",
+            "B": "y",
+        })));
+    }
+}
@@ -0,0 +1,94 @@
+use {
+    crate::{
+        supervalue::Supervalue,
+        supervalue_path::DataPath,
+    },
+    std::fmt::Display,
+};
+
+/// Errors from the data manipulation functions. Paths are the path of the
+/// container where the problem was found (i.e. the path up to but not including
+/// the problematic segment).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A map along the path doesn't have the key.
+    MissingKey {
+        path: DataPath,
+        key: String,
+    },
+    /// An array along the path doesn't have the index.
+    OutOfBounds {
+        path: DataPath,
+        index: usize,
+    },
+    /// A primitive value (not a map or array) was found before the end of the path.
+    Untraversable {
+        path: DataPath,
+    },
+    /// The path segment can't be used to index the value at the path, like a number
+    /// for a map key or a non-numeric string or negative number for an array index.
+    TypeMismatch {
+        path: DataPath,
+        segment: serde_json::Value,
+    },
+    /// A value being searched for wasn't found anywhere in the data.
+    NeedleNotFound {
+        needle: Supervalue,
+    },
+    /// A flattened key refers to a value that another key treats as a map or
+    /// array, or vice versa (ex: both `a` and `a.b`), or two paths flatten to the
+    /// same key.
+    FlattenConflict {
+        key: String,
+    },
+    /// Several independent errors (ex: from subtracting multiple paths).
+    Multiple(Vec<Error>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingKey { path, key } => {
+                return write!(
+                    f,
+                    "Encountered object value at {} but the key [{}] is missing",
+                    path,
+                    serde_json::to_string(key).unwrap()
+                );
+            },
+            Error::OutOfBounds { path, index } => {
+                return write!(f, "Encountered array value at {} but the index [{}] is out of bounds", path, index);
+            },
+            Error::Untraversable { path } => {
+                return write!(f, "Encountered primitive value at {}, before reaching end of path", path);
+            },
+            Error::TypeMismatch { path, segment } => {
+                return write!(
+                    f,
+                    "Path segment [{}] can't be used to index the value at {}",
+                    serde_json::to_string(segment).unwrap(),
+                    path
+                );
+            },
+            Error::NeedleNotFound { needle } => {
+                return write!(
+                    f,
+                    "Couldn't find needle {}",
+                    serde_json::to_string(&<Supervalue as Into<serde_json::Value>>::into(needle.clone())).unwrap()
+                );
+            },
+            Error::FlattenConflict { key } => {
+                return write!(
+                    f,
+                    "Flattened key [{}] conflicts with another key",
+                    serde_json::to_string(key).unwrap()
+                );
+            },
+            Error::Multiple(errors) => {
+                return write!(f, "{}", errors.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n"));
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error { }
@@ -0,0 +1,320 @@
+use {
+    crate::{
+        error::Error,
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+            SupervalueVec,
+            SupervalueVecType,
+        },
+    },
+    std::collections::{
+        BTreeMap,
+        HashMap,
+    },
+};
+
+/// How array indices are written in flattened keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayStyle {
+    /// Like other segments: `a.0.b`
+    #[default]
+    Separator,
+    /// Bracketed, appended to the previous segment: `a[0].b`
+    Brackets,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlattenOptions {
+    pub separator: String,
+    pub array_style: ArrayStyle,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        return FlattenOptions {
+            separator: ".".to_string(),
+            array_style: ArrayStyle::Separator,
+        };
+    }
+}
+
+fn flatten_into(
+    out: &mut HashMap<String, Supervalue>,
+    prefix: Option<String>,
+    value: Supervalue,
+    options: &FlattenOptions,
+) -> Result<(), Error> {
+    match value {
+        Supervalue::Map(map) if !map.value.is_empty() => {
+            for (k, v) in map.value {
+                let key = match &prefix {
+                    Some(prefix) => format!("{}{}{}", prefix, options.separator, k),
+                    None => k,
+                };
+                flatten_into(out, Some(key), v, options)?;
+            }
+        },
+        Supervalue::Vec(vec) if !vec.value.is_empty() => {
+            for (i, v) in vec.value.into_iter().enumerate() {
+                let key = match (&prefix, options.array_style) {
+                    (Some(prefix), ArrayStyle::Separator) => format!("{}{}{}", prefix, options.separator, i),
+                    (None, ArrayStyle::Separator) => i.to_string(),
+                    (Some(prefix), ArrayStyle::Brackets) => format!("{}[{}]", prefix, i),
+                    (None, ArrayStyle::Brackets) => format!("[{}]", i),
+                };
+                flatten_into(out, Some(key), v, options)?;
+            }
+        },
+        v => {
+            let key = prefix.unwrap_or_default();
+            if out.contains_key(&key) {
+                return Err(Error::FlattenConflict { key: key });
+            }
+            out.insert(key, v);
+        },
+    }
+    return Ok(());
+}
+
+/// Convert nested data into a single level map, with keys made by joining the
+/// path segments to each primitive value (or empty map or array). Fails if two
+/// paths join to the same key (ex: `{"a.b": 1, "a": {"b": 2}}`).
+pub fn flatten(source: Supervalue, options: &FlattenOptions) -> Result<Supervalue, Error> {
+    let mut out = HashMap::new();
+    flatten_into(&mut out, None, source, options)?;
+    return Ok(Supervalue::Map(SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: out,
+    }));
+}
+
+/// Parse an array index segment, only accepting the form `flatten` writes (no
+/// leading zeros or signs).
+fn parse_index(s: &str) -> Option<usize> {
+    let i = s.parse::<usize>().ok()?;
+    if i.to_string() != s {
+        return None;
+    }
+    return Some(i);
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn split_key(key: &str, options: &FlattenOptions) -> Vec<Segment> {
+    let mut out = vec![];
+    if key.is_empty() {
+        // The root, from flattening a primitive
+        return out;
+    }
+    let parts = if options.separator.is_empty() {
+        vec![key]
+    } else {
+        key.split(options.separator.as_str()).collect::<Vec<_>>()
+    };
+    for part in parts {
+        match options.array_style {
+            ArrayStyle::Separator => {
+                match parse_index(part) {
+                    Some(i) => out.push(Segment::Index(i)),
+                    None => out.push(Segment::Key(part.to_string())),
+                }
+            },
+            ArrayStyle::Brackets => {
+                let mut name = part;
+                let mut indices = vec![];
+                while let Some(rest) = name.strip_suffix("]") {
+                    let Some((before, index)) = rest.rsplit_once("[") else {
+                        break;
+                    };
+                    let Some(index) = parse_index(index) else {
+                        break;
+                    };
+                    indices.push(index);
+                    name = before;
+                }
+                if !name.is_empty() || indices.is_empty() {
+                    out.push(Segment::Key(name.to_string()));
+                }
+                out.extend(indices.into_iter().rev().map(Segment::Index));
+            },
+        }
+    }
+    return out;
+}
+
+enum Node {
+    Empty,
+    Leaf(Supervalue),
+    Map(BTreeMap<String, Node>),
+    Vec(BTreeMap<usize, Node>),
+}
+
+impl Node {
+    fn into_supervalue(self) -> Supervalue {
+        match self {
+            Node::Empty => return Supervalue::Null,
+            Node::Leaf(v) => return v,
+            Node::Map(m) => return Supervalue::Map(SupervalueMap {
+                type_: SupervalueMapType::Normal,
+                value: m.into_iter().map(|(k, v)| (k, v.into_supervalue())).collect(),
+            }),
+            Node::Vec(m) => {
+                if m.keys().next_back().is_some_and(|i| *i + 1 != m.len()) {
+                    // Sparse indices, probably numeric map keys
+                    return Supervalue::Map(SupervalueMap {
+                        type_: SupervalueMapType::Normal,
+                        value: m.into_iter().map(|(k, v)| (k.to_string(), v.into_supervalue())).collect(),
+                    });
+                }
+                return Supervalue::Vec(SupervalueVec {
+                    type_: SupervalueVecType::Normal,
+                    value: m.into_values().map(|v| v.into_supervalue()).collect(),
+                });
+            },
+        }
+    }
+}
+
+/// Reverse `flatten`, splitting each key of a single level map into path
+/// segments and building nested data. Numeric segments (or bracketed indices
+/// with `ArrayStyle::Brackets`) become arrays if they're dense from 0, otherwise
+/// they're map keys (ex: `ports.8080`). Data that isn't a map is returned as is.
+pub fn unflatten(source: Supervalue, options: &FlattenOptions) -> Result<Supervalue, Error> {
+    let Supervalue::Map(map) = source else {
+        return Ok(source);
+    };
+    let mut root = Node::Empty;
+    for (key, value) in map.value {
+        let conflict = || Error::FlattenConflict { key: key.clone() };
+        let mut at = &mut root;
+        for seg in split_key(&key, options) {
+            match seg {
+                Segment::Key(k) => {
+                    match at {
+                        Node::Empty => *at = Node::Map(BTreeMap::new()),
+                        Node::Vec(m) => {
+                            // Mixed with non-numeric keys, so the indices were map keys too
+                            *at =
+                                Node::Map(
+                                    std::mem::take(m).into_iter().map(|(i, v)| (i.to_string(), v)).collect(),
+                                );
+                        },
+                        _ => { },
+                    }
+                    let Node::Map(m) = at else {
+                        return Err(conflict());
+                    };
+                    at = m.entry(k).or_insert(Node::Empty);
+                },
+                Segment::Index(i) => {
+                    if let Node::Empty = at {
+                        *at = Node::Vec(BTreeMap::new());
+                    }
+                    match at {
+                        Node::Vec(m) => at = m.entry(i).or_insert(Node::Empty),
+                        Node::Map(m) => at = m.entry(i.to_string()).or_insert(Node::Empty),
+                        _ => return Err(conflict()),
+                    }
+                },
+            }
+        }
+        let Node::Empty = at else {
+            return Err(conflict());
+        };
+        *at = Node::Leaf(value);
+    }
+    return Ok(root.into_supervalue());
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            flatten,
+            unflatten,
+            ArrayStyle,
+            FlattenOptions,
+        },
+        crate::{
+            error::Error,
+            supervalue::Supervalue,
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn round_trip() {
+        let source = Supervalue::from(json!({
+            "a": {
+                "b": [1, {
+                    "c": "x"
+                }],
+                "e": {},
+            },
+        }));
+        let options = FlattenOptions::default();
+        let flat = flatten(source.clone(), &options).unwrap();
+        assert_eq!(flat, Supervalue::from(json!({
+            "a.b.0": 1,
+            "a.b.1.c": "x",
+            "a.e": {},
+        })));
+        assert_eq!(unflatten(flat, &options).unwrap(), source);
+        let options = FlattenOptions {
+            separator: "__".to_string(),
+            array_style: ArrayStyle::Brackets,
+        };
+        let flat = flatten(source.clone(), &options).unwrap();
+        assert_eq!(flat, Supervalue::from(json!({
+            "a__b[0]": 1,
+            "a__b[1]__c": "x",
+            "a__e": {},
+        })));
+        assert_eq!(unflatten(flat, &options).unwrap(), source);
+    }
+
+    #[test]
+    fn conflict() {
+        assert_eq!(unflatten(Supervalue::from(json!({
+            "a": 1,
+            "a.b": 2,
+        })), &FlattenOptions::default()).err().map(|e| matches!(e, Error::FlattenConflict { .. })), Some(true));
+    }
+
+    #[test]
+    fn flatten_conflict() {
+        assert_eq!(flatten(Supervalue::from(json!({
+            "a.b": 1,
+            "a": {
+                "b": 2
+            },
+        })), &FlattenOptions::default()).err(), Some(Error::FlattenConflict { key: "a.b".to_string() }));
+    }
+
+    #[test]
+    fn sparse_indices() {
+        let source = Supervalue::from(json!({
+            "ports": {
+                "8080": "http",
+                "0443": "https",
+            },
+        }));
+        let options = FlattenOptions::default();
+        assert_eq!(unflatten(flatten(source.clone(), &options).unwrap(), &options).unwrap(), source);
+        assert_eq!(unflatten(Supervalue::from(json!({
+            "a[100000000000]": "x",
+        })), &FlattenOptions {
+            separator: ".".to_string(),
+            array_style: ArrayStyle::Brackets,
+        }).unwrap(), Supervalue::from(json!({
+            "a": {
+                "100000000000": "x"
+            },
+        })));
+    }
+}
@@ -0,0 +1,97 @@
+use {
+    crate::{
+        supervalue::{
+            Supervalue,
+            SupervalueVec,
+            SupervalueVecType,
+        },
+        supervalue_path::DataPath,
+    },
+};
+
+fn force_array_segments(at: &mut Supervalue, path: &[serde_json::Value]) {
+    let Some((seg, rest)) = path.split_first() else {
+        if !matches!(at, Supervalue::Vec(_)) {
+            let value = std::mem::replace(at, Supervalue::Null);
+            *at = Supervalue::Vec(SupervalueVec {
+                type_: SupervalueVecType::Normal,
+                value: vec![value],
+            });
+        }
+        return;
+    };
+    match at {
+        Supervalue::Map(map) => {
+            let serde_json::Value::String(key) = seg else {
+                return;
+            };
+            if let Some(child) = map.value.get_mut(key) {
+                force_array_segments(child, rest);
+            }
+        },
+        Supervalue::Vec(vec) => {
+            let index = match seg {
+                serde_json::Value::String(s) => s.parse::<usize>().ok(),
+                serde_json::Value::Number(n) => n.as_u64().map(|n| n as usize),
+                _ => None,
+            };
+            match index {
+                Some(index) => {
+                    if let Some(child) = vec.value.get_mut(index) {
+                        force_array_segments(child, rest);
+                    }
+                },
+                None => {
+                    // Not an index, apply to every element
+                    for child in &mut vec.value {
+                        force_array_segments(child, path);
+                    }
+                },
+            }
+        },
+        _ => { },
+    }
+}
+
+/// Wrap the value at `path` in a single element array if it isn't already an
+/// array. This is meant for data from formats like XML where a repeated element
+/// only becomes an array if it occurs multiple times.
+///
+/// Arrays along the path that aren't indexed by the next segment are traversed
+/// element-wise (i.e. `.a.b` applies to `b` in every element if `a` is an
+/// array). Missing values are ignored.
+pub fn force_array(source: &mut Supervalue, path: &DataPath) {
+    force_array_segments(source, &path.0);
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::force_array,
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn force() {
+        let mut source = Supervalue::from(json!({
+            "deps": [{
+                "dep": "a"
+            }, {
+                "dep": ["b", "c"]
+            }, {}],
+            "one": null,
+        }));
+        force_array(&mut source, &".deps.dep".parse().unwrap());
+        force_array(&mut source, &".one".parse().unwrap());
+        force_array(&mut source, &".missing.x".parse().unwrap());
+        assert_eq!(source, Supervalue::from(json!({
+            "deps": [{
+                "dep": ["a"]
+            }, {
+                "dep": ["b", "c"]
+            }, {}],
+            "one": [null],
+        })));
+    }
+}
@@ -1,5 +1,6 @@
 use {
     crate::{
+        error::Error,
         supervalue::Supervalue,
         supervalue_path::DataPath,
         utils::{
@@ -12,7 +13,7 @@ use {
 };
 
 /// Can only error if `!missing_ok`.
-pub fn get(root: &mut Supervalue, path: &DataPath, missing_ok: bool) -> Result<Option<Supervalue>, String> {
+pub fn get(root: &mut Supervalue, path: &DataPath, missing_ok: bool) -> Result<Option<Supervalue>, Error> {
     return Ok(at_path(
         //. .
         path,
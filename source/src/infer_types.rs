@@ -0,0 +1,109 @@
+use {
+    crate::{
+        supervalue::Supervalue,
+        supervalue_datetime::SupervalueDatetime,
+    },
+};
+
+/// Recursively replace strings that look like JSON numbers or booleans (`true`,
+/// `false`) with numbers or booleans. For data from formats without types, like
+/// CSV or XML. Strings with surrounding whitespace or leading zeros are kept as
+/// strings.
+pub fn infer_types(source: &mut Supervalue) {
+    match source {
+        Supervalue::Map(map) => {
+            for v in map.value.values_mut() {
+                infer_types(v);
+            }
+        },
+        Supervalue::Vec(vec) => {
+            for v in &mut vec.value {
+                infer_types(v);
+            }
+        },
+        Supervalue::String(s) => {
+            if s == "true" {
+                *source = Supervalue::Bool(true);
+            } else if s == "false" {
+                *source = Supervalue::Bool(false);
+            } else if s.trim() == s && !s.is_empty() {
+                if let Ok(n) = serde_json::from_str::<serde_json::Number>(s) {
+                    *source = Supervalue::Number(n.into());
+                }
+            }
+        },
+        _ => { },
+    }
+}
+
+/// Recursively replace strings in RFC 3339 or YAML timestamp format (like
+/// `2001-12-14`, `2001-12-14T21:59:43Z` or `2001-12-14 21:59:43.10 -5`) with
+/// datetimes.
+pub fn infer_datetimes(source: &mut Supervalue) {
+    match source {
+        Supervalue::Map(map) => {
+            for v in map.value.values_mut() {
+                infer_datetimes(v);
+            }
+        },
+        Supervalue::Vec(vec) => {
+            for v in &mut vec.value {
+                infer_datetimes(v);
+            }
+        },
+        Supervalue::String(s) => {
+            if let Some(d) = SupervalueDatetime::parse(s) {
+                *source = Supervalue::Datetime(d);
+            }
+        },
+        _ => { },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            infer_datetimes,
+            infer_types,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn infer() {
+        let mut source = Supervalue::from(json!([{
+            "a": "12",
+            "b": "-1.5",
+            "c": "true",
+            "d": "007",
+            "e": " 1",
+            "f": "",
+            "g": "x",
+        }]));
+        infer_types(&mut source);
+        assert_eq!(source, Supervalue::from(json!([{
+            "a": 12,
+            "b": -1.5,
+            "c": true,
+            "d": "007",
+            "e": " 1",
+            "f": "",
+            "g": "x",
+        }])));
+    }
+
+    #[test]
+    fn datetimes() {
+        let mut source = Supervalue::from(json!({
+            "a": "2001-12-14 21:59:43.10 -5",
+            "b": ["2001-12-14", "21:59:43", "x"],
+        }));
+        infer_datetimes(&mut source);
+        assert_eq!(
+            source,
+            Supervalue::from(toml::from_str::<toml::Value>("a = 2001-12-15T02:59:43.1Z\nb = [2001-12-14, \"21:59:43\", \"x\"]\n").unwrap())
+        );
+    }
+}
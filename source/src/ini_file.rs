@@ -0,0 +1,115 @@
+use {
+    crate::{
+        env_file::primitive_text,
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+        },
+    },
+    ini::Ini,
+};
+
+/// Parse an INI file into a map. Keys before the first section are at the top
+/// level, and each section is a nested map. All values are strings.
+pub fn from_ini_str(text: &str) -> Result<Supervalue, String> {
+    let ini = Ini::load_from_str(text).map_err(|e| e.to_string())?;
+    let mut out = SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: Default::default(),
+    };
+    for (section, properties) in &ini {
+        let props = properties.iter().map(|(k, v)| (k.to_string(), Supervalue::String(v.to_string())));
+        match section {
+            None => {
+                out.value.extend(props);
+            },
+            Some(section) => {
+                let entry = out.value.entry(section.to_string()).or_insert_with(|| Supervalue::Map(SupervalueMap {
+                    type_: SupervalueMapType::Normal,
+                    value: Default::default(),
+                }));
+                let Supervalue::Map(entry) = entry else {
+                    return Err(format!("Section [{}] has the same name as a top level key", section));
+                };
+                entry.value.extend(props);
+            },
+        }
+    }
+    return Ok(Supervalue::Map(out));
+}
+
+/// Write a map as an INI file. Top level primitives are written before any
+/// sections, and top level maps of primitives become sections.
+pub fn to_ini_string(value: &Supervalue) -> Result<String, String> {
+    let Supervalue::Map(map) = value else {
+        return Err(format!("INI output requires a map, but the data isn't a map"));
+    };
+    let mut ini = Ini::new();
+    let mut keys = map.value.keys().collect::<Vec<_>>();
+    keys.sort();
+    for k in &keys {
+        if let Some(v) = primitive_text(&map.value[*k]) {
+            ini.with_general_section().set(k.as_str(), v);
+        }
+    }
+    for k in &keys {
+        let v = &map.value[*k];
+        if primitive_text(v).is_some() {
+            continue;
+        }
+        let Supervalue::Map(section) = v else {
+            return Err(format!("Value at [{}] is an array, but INI files can't contain arrays", k));
+        };
+        let mut section_keys = section.value.keys().collect::<Vec<_>>();
+        section_keys.sort();
+        let mut setter = ini.with_section(Some(k.as_str()));
+        for sk in section_keys {
+            let Some(sv) = primitive_text(&section.value[sk]) else {
+                return Err(
+                    format!(
+                        "Value at [{}, {}] is a map or array, but INI sections can only contain primitive values (the data is too deep)",
+                        k,
+                        sk
+                    ),
+                );
+            };
+            setter.set(sk.as_str(), sv);
+        }
+    }
+    let mut out = vec![];
+    ini.write_to(&mut out).map_err(|e| e.to_string())?;
+    return Ok(String::from_utf8(out).unwrap());
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_ini_str,
+            to_ini_string,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn round_trip() {
+        let source = from_ini_str("top=1\n; comment\n[server]\nhost = example.com\nport=80\n").unwrap();
+        assert_eq!(source, Supervalue::from(json!({
+            "top": "1",
+            "server": {
+                "host": "example.com",
+                "port": "80",
+            },
+        })));
+        assert_eq!(from_ini_str(&to_ini_string(&source).unwrap()).unwrap(), source);
+        assert!(to_ini_string(&Supervalue::from(json!({
+            "a": {
+                "b": {
+                    "c": 1
+                }
+            }
+        }))).is_err());
+    }
+}
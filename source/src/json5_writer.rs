@@ -0,0 +1,97 @@
+use {
+    crate::supervalue::Supervalue,
+    base64::prelude::{
+        BASE64_STANDARD,
+        Engine,
+    },
+    std::fmt::Write,
+};
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    if !(first.is_ascii_alphabetic() || first == '_' || first == '$') {
+        return false;
+    }
+    return chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+}
+
+fn write_indent(out: &mut String, depth: usize) {
+    for _ in 0 .. depth {
+        out.push_str("  ");
+    }
+}
+
+fn write_value(out: &mut String, value: &Supervalue, depth: usize) {
+    match value {
+        Supervalue::Map(v) => {
+            if v.value.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            let mut keys = v.value.keys().collect::<Vec<_>>();
+            keys.sort();
+            out.push_str("{\n");
+            for k in keys {
+                write_indent(out, depth + 1);
+                if is_identifier(k) {
+                    out.push_str(k);
+                } else {
+                    out.push_str(&serde_json::to_string(k).unwrap());
+                }
+                out.push_str(": ");
+                write_value(out, &v.value[k], depth + 1);
+                out.push_str(",\n");
+            }
+            write_indent(out, depth);
+            out.push('}');
+        },
+        Supervalue::Vec(v) => {
+            if v.value.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push_str("[\n");
+            for e in &v.value {
+                write_indent(out, depth + 1);
+                write_value(out, e, depth + 1);
+                out.push_str(",\n");
+            }
+            write_indent(out, depth);
+            out.push(']');
+        },
+        Supervalue::Null => out.push_str("null"),
+        Supervalue::Bool(v) => write!(out, "{}", v).unwrap(),
+        Supervalue::String(v) => out.push_str(&serde_json::to_string(v).unwrap()),
+        Supervalue::Number(v) => write!(out, "{}", v).unwrap(),
+        Supervalue::Datetime(v) => out.push_str(&serde_json::to_string(&v.to_string()).unwrap()),
+        Supervalue::Bytes(v) => out.push_str(&serde_json::to_string(&BASE64_STANDARD.encode(v)).unwrap()),
+    }
+}
+
+/// Write the value as indented JSON5, with unquoted keys where possible and
+/// trailing commas. Non-finite numbers are written as `NaN`/`Infinity`.
+pub fn to_json5_string_pretty(value: &Supervalue) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, 0);
+    out.push('\n');
+    return out;
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::to_json5_string_pretty,
+        crate::supervalue::Supervalue,
+    };
+
+    #[test]
+    fn round_trip() {
+        let source = json5::from_str::<Supervalue>("{a: 0x10, 'b-c': [Infinity, 'x'], d: {}}").unwrap();
+        let text = to_json5_string_pretty(&source);
+        assert_eq!(text, "{\n  a: 16,\n  \"b-c\": [\n    Infinity,\n    \"x\",\n  ],\n  d: {},\n}\n");
+        assert_eq!(json5::from_str::<Supervalue>(&text).unwrap(), source);
+    }
+}
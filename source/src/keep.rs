@@ -1,5 +1,6 @@
 use {
     crate::{
+        error::Error,
         merge::merge,
         set::set,
         supervalue::Supervalue,
@@ -19,7 +20,7 @@ pub fn keep(
     out: &mut Option<Supervalue>,
     path: &DataPath,
     missing_ok: bool,
-) -> Result<(), String> {
+) -> Result<(), Error> {
     let out = RefCell::new(out);
     at_path(
         //. .
@@ -1,6 +1,8 @@
 pub mod utils;
 pub mod supervalue;
 pub mod supervalue_path;
+pub mod supervalue_number;
+pub mod supervalue_datetime;
 pub mod get;
 pub mod set;
 pub mod keep;
@@ -10,3 +12,21 @@ pub mod search_delete;
 pub mod intersect;
 pub mod subtract;
 pub mod merge;
+pub mod codegen;
+pub mod error;
+pub mod api;
+pub mod supervalue_serde;
+pub mod json5_writer;
+pub mod xml;
+pub mod force_array;
+pub mod table;
+pub mod infer_types;
+pub mod env_file;
+pub mod ini_file;
+pub mod flatten;
+pub mod properties;
+pub mod binary;
+pub mod yaml_tags;
+pub mod yaml_anchors;
+pub mod atomic_write;
+pub mod check;
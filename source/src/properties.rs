@@ -0,0 +1,179 @@
+use {
+    crate::{
+        env_file::primitive_text,
+        flatten::{
+            flatten,
+            unflatten,
+            ArrayStyle,
+            FlattenOptions,
+        },
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+        },
+    },
+};
+
+/// Properties keys are dotted, with bracketed array indices like Spring's
+/// `list[0].name`.
+pub fn properties_flatten_options() -> FlattenOptions {
+    return FlattenOptions {
+        separator: ".".to_string(),
+        array_style: ArrayStyle::Brackets,
+    };
+}
+
+fn unescape(line_number: usize, chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<char, String> {
+    match chars.next() {
+        Some('t') => return Ok('\t'),
+        Some('n') => return Ok('\n'),
+        Some('r') => return Ok('\r'),
+        Some('f') => return Ok('\x0c'),
+        Some('u') => {
+            let hex = chars.by_ref().take(4).collect::<String>();
+            let c =
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("Line {}: invalid unicode escape [\\u{}]", line_number, hex))?;
+            return Ok(c);
+        },
+        Some(c) => return Ok(c),
+        None => return Err(format!("Line {}: incomplete escape", line_number)),
+    }
+}
+
+/// Parse a Java `.properties` file into a flat map of strings.
+fn from_properties_str_flat(text: &str) -> Result<Supervalue, String> {
+    let mut out = SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: Default::default(),
+    };
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line_number = i + 1;
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with("#") || line.starts_with("!") {
+            continue;
+        }
+
+        // Join continuation lines
+        let mut logical = line.to_string();
+        while logical.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+            logical.pop();
+            let Some((_, next)) = lines.next() else {
+                break;
+            };
+            logical.push_str(next.trim_start());
+        }
+
+        // Split key and value
+        let mut chars = logical.chars().peekable();
+        let mut key = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => key.push(unescape(line_number, &mut chars)?),
+                '=' | ':' => break,
+                c if c.is_whitespace() => {
+                    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                        chars.next();
+                    }
+                    if chars.peek().is_some_and(|c| *c == '=' || *c == ':') {
+                        chars.next();
+                    }
+                    break;
+                },
+                c => key.push(c),
+            }
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => value.push(unescape(line_number, &mut chars)?),
+                c => value.push(c),
+            }
+        }
+        out.value.insert(key, Supervalue::String(value));
+    }
+    return Ok(Supervalue::Map(out));
+}
+
+/// Parse a Java `.properties` file, unflattening the keys into nested data.
+pub fn from_properties_str(text: &str) -> Result<Supervalue, String> {
+    return unflatten(from_properties_str_flat(text)?, &properties_flatten_options()).map_err(|e| e.to_string());
+}
+
+fn escape(out: &mut String, text: &str, is_key: bool) {
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\x0c' => out.push_str("\\f"),
+            '=' | ':' | '#' | '!' if is_key => {
+                out.push('\\');
+                out.push(c);
+            },
+            ' ' if is_key || i == 0 => out.push_str("\\ "),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Flatten data and write it as a Java `.properties` file. Empty maps and arrays
+/// are written as empty values.
+pub fn to_properties_string(value: &Supervalue) -> Result<String, String> {
+    let Supervalue::Map(flat) = flatten(value.clone(), &properties_flatten_options()).map_err(|e| e.to_string())? else {
+        unreachable!();
+    };
+    let mut keys = flat.value.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut out = String::new();
+    for k in keys {
+        if k.is_empty() {
+            return Err(format!("Properties output requires a map or array, but the data is a primitive"));
+        }
+        let v = primitive_text(&flat.value[k]).unwrap_or_default();
+        escape(&mut out, k, true);
+        out.push('=');
+        escape(&mut out, &v, false);
+        out.push('\n');
+    }
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_properties_str,
+            to_properties_string,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn round_trip() {
+        let source =
+            from_properties_str(
+                "# comment\n! comment\nserver.port = 8080\nserver.hosts[0]=a\nserver.hosts[1]:b\nmsg=multi \\\n    line\\u0021\nkey\\ with\\ space value\n",
+            ).unwrap();
+        assert_eq!(source, Supervalue::from(json!({
+            "server": {
+                "port": "8080",
+                "hosts": ["a", "b"],
+            },
+            "msg": "multi line!",
+            "key with space": "value",
+        })));
+        let text = to_properties_string(&source).unwrap();
+        assert_eq!(text, "key\\ with\\ space=value\nmsg=multi line!\nserver.hosts[0]=a\nserver.hosts[1]=b\nserver.port=8080\n");
+        assert_eq!(from_properties_str(&text).unwrap(), source);
+    }
+}
@@ -1,5 +1,6 @@
 use {
     crate::{
+        error::Error,
         supervalue::Supervalue,
         supervalue_path::DataPath,
         utils::{
@@ -11,7 +12,7 @@ use {
     },
 };
 
-pub fn set(dest: &mut Supervalue, path: &DataPath, value: &Supervalue, missing_ok: bool) -> Result<(), String> {
+pub fn set(dest: &mut Supervalue, path: &DataPath, value: &Supervalue, missing_ok: bool) -> Result<(), Error> {
     return at_path(
         //. .
         path,
@@ -1,26 +1,31 @@
 use {
-    crate::supervalue::{
-        Supervalue,
-        SupervalueMap,
+    crate::{
+        error::Error,
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+        },
+        supervalue_path::DataPath,
     },
 };
 
 fn recurse<
     'a,
 >(
-    errors: &mut Vec<String>,
+    errors: &mut Vec<Error>,
     path: &mut Vec<&'a str>,
     source: &mut SupervalueMap,
     other: &'a SupervalueMap,
     missing_ok: bool,
 ) {
     for (k, other_val) in &other.value {
-        path.push(k);
         if let Some(source_val) = source.value.get_mut(k) {
             if source_val == other_val {
                 source.value.remove(k);
             } else if let (Supervalue::Map(source), Supervalue::Map(other)) = (source_val, other_val) {
+                path.push(k);
                 recurse(errors, path, source, other, missing_ok);
+                path.pop();
             } else {
                 // nop
             }
@@ -28,14 +33,16 @@ fn recurse<
             if missing_ok {
                 // nop
             } else {
-                errors.push(format!("Trying to subtract path [{:?}] but no value exists at that path", path));
+                errors.push(Error::MissingKey {
+                    path: DataPath(path.iter().map(|k| serde_json::Value::String(k.to_string())).collect()),
+                    key: k.clone(),
+                });
             }
         }
-        path.pop();
     }
 }
 
-pub fn subtract(source: &mut Supervalue, other: &Supervalue, missing_ok: bool) -> Result<(), String> {
+pub fn subtract(source: &mut Supervalue, other: &Supervalue, missing_ok: bool) -> Result<(), Error> {
     let mut layer_errors = vec![];
     if source == other {
         *source = Supervalue::Null;
@@ -45,7 +52,7 @@ pub fn subtract(source: &mut Supervalue, other: &Supervalue, missing_ok: bool) -
         // nop
     }
     if !layer_errors.is_empty() {
-        return Err(layer_errors.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n"));
+        return Err(Error::Multiple(layer_errors));
     }
     return Ok(());
 }
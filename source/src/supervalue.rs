@@ -1,10 +1,33 @@
 use {
+    crate::{
+        binary::{
+            from_cbor_slice,
+            from_msgpack_slice,
+        },
+        env_file::from_env_str,
+        ini_file::from_ini_str,
+        properties::from_properties_str,
+        supervalue_datetime::SupervalueDatetime,
+        supervalue_number::{
+            NumberPrimitive,
+            SupervalueNumber,
+        },
+        table::from_csv_str,
+        xml::from_xml_str,
+        yaml_anchors::{
+            find_yaml_anchors,
+            yaml_scalar_lines,
+            YamlAnchor,
+        },
+    },
+    base64::prelude::{
+        BASE64_STANDARD,
+        Engine,
+    },
     aargvark::traits_impls::{
         AargvarkFile,
         AargvarkFromStr,
         AargvarkJson,
-        AargvarkToml,
-        AargvarkYaml,
     },
     flowcontrol::{
         exenum,
@@ -12,17 +35,60 @@ use {
     },
     jsonc_to_json::jsonc_to_json,
     samevariant::samevariant,
-    std::collections::HashMap,
+    std::{
+        collections::{
+            HashMap,
+            HashSet,
+        },
+        path::Path,
+    },
 };
 
-const YAML_TAG_TAG: &str = "tag";
-const YAML_TAG_VALUE: &str = "tag";
+/// The key of the tag (a string, like `!Ref`) in a tagged value map.
+pub const YAML_TAG_TAG: &str = "tag";
+/// The key of the inner value in a tagged value map.
+pub const YAML_TAG_VALUE: &str = "value";
+
+/// How to write `null` to TOML, which doesn't have nulls.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TomlNull {
+    /// Leave out map entries and array elements that are `null`
+    #[default]
+    Omit,
+    /// Fail the conversion
+    Error,
+    /// Write an empty string
+    Empty,
+}
+
+/// Options for converting data to other formats.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Convert numbers the target format can't represent exactly to the nearest
+    /// representable value rather than failing.
+    pub lossy_numbers: bool,
+    pub toml_null: TomlNull,
+}
+
+impl ConvertOptions {
+    pub fn lossy() -> Self {
+        return ConvertOptions {
+            lossy_numbers: true,
+            toml_null: TomlNull::Omit,
+        };
+    }
+}
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub enum SupervalueMapType {
     #[default]
     Normal,
     YamlTag,
+    /// A map with some non-string keys (from YAML, RON, CBOR, etc.). Those keys are
+    /// stored as their compact JSON serialization (so the integer key `1` can be
+    /// addressed with the path `.1`, and `[1, 2]` with `["[1,2]"]`) and listed here
+    /// so they can be restored for formats that support them.
+    ComplexKeys(HashSet<String>),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -31,6 +97,36 @@ pub struct SupervalueMap {
     pub value: HashMap<String, Supervalue>,
 }
 
+impl SupervalueMap {
+    /// A tagged value (from YAML, or CBOR and MessagePack). This is a map with the
+    /// keys `tag` and `value` so both can be addressed by paths.
+    pub fn new_tagged(tag: String, value: Supervalue) -> SupervalueMap {
+        let mut out = HashMap::new();
+        out.insert(YAML_TAG_TAG.to_string(), Supervalue::String(tag));
+        out.insert(YAML_TAG_VALUE.to_string(), value);
+        return SupervalueMap {
+            type_: SupervalueMapType::YamlTag,
+            value: out,
+        };
+    }
+
+    /// The tag and inner value, if this is a tagged value. If it was modified so it
+    /// no longer has exactly a string `tag` and a `value` it's treated as a normal
+    /// map.
+    pub fn as_tagged(&self) -> Option<(&str, &Supervalue)> {
+        if self.type_ != SupervalueMapType::YamlTag || self.value.len() != 2 {
+            return None;
+        }
+        let Some(Supervalue::String(tag)) = self.value.get(YAML_TAG_TAG) else {
+            return None;
+        };
+        let Some(value) = self.value.get(YAML_TAG_VALUE) else {
+            return None;
+        };
+        return Some((tag, value));
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SupervalueVecType {
     Normal,
@@ -51,9 +147,13 @@ pub enum Supervalue {
     Null,
     Bool(bool),
     String(String),
-    JsonNumber(serde_json::Number),
-    YamlNumber(serde_yaml::Number),
-    TomlDatetime(toml::value::Datetime),
+    Number(SupervalueNumber),
+    /// A date and/or time, from TOML, CBOR, or strings with `infer-datetimes`.
+    /// Converted to RFC 3339 strings for formats without a datetime type.
+    Datetime(SupervalueDatetime),
+    /// Binary data, from CBOR or MessagePack. Converted to base64 strings for
+    /// formats without a binary type.
+    Bytes(Vec<u8>),
 }
 
 impl PartialEq for Supervalue {
@@ -64,9 +164,9 @@ impl PartialEq for Supervalue {
             SupervalueSamevariant::Vec(a, b) => return *a == *b,
             SupervalueSamevariant::Bool(a, b) => return *a == *b,
             SupervalueSamevariant::String(a, b) => return *a == *b,
-            SupervalueSamevariant::JsonNumber(a, b) => return *a == *b,
-            SupervalueSamevariant::YamlNumber(a, b) => return *a == *b,
-            SupervalueSamevariant::TomlDatetime(a, b) => return *a == *b,
+            SupervalueSamevariant::Number(a, b) => return *a == *b,
+            SupervalueSamevariant::Datetime(a, b) => return *a == *b,
+            SupervalueSamevariant::Bytes(a, b) => return *a == *b,
             SupervalueSamevariant::Nonmatching(_, _) => return false,
         }
     }
@@ -74,12 +174,79 @@ impl PartialEq for Supervalue {
 
 impl Eq for Supervalue { }
 
+impl Supervalue {
+    /// Build a map from entries with keys of any type. If there are non-string keys
+    /// this is a `SupervalueMapType::ComplexKeys` map, unless two keys have the same
+    /// JSON serialization, in which case it's an array of key/value pairs
+    /// (`SupervalueVecType::YamlMap`).
+    pub fn from_pairs(pairs: Vec<(Supervalue, Supervalue)>) -> Supervalue {
+        let keys = pairs.iter().map(|(k, _)| match k {
+            Supervalue::String(k) => (k.clone(), false),
+            k => (serde_json::to_string(&<Supervalue as Into<serde_json::Value>>::into(k.clone())).unwrap(), true),
+        }).collect::<Vec<_>>();
+        if keys.iter().map(|(k, _)| k).collect::<HashSet<_>>().len() == keys.len() {
+            let complex = keys.iter().filter(|(_, complex)| *complex).map(|(k, _)| k.clone()).collect::<HashSet<_>>();
+            return Supervalue::Map(SupervalueMap {
+                type_: if complex.is_empty() {
+                    SupervalueMapType::Normal
+                } else {
+                    SupervalueMapType::ComplexKeys(complex)
+                },
+                value: keys.into_iter().map(|(k, _)| k).zip(pairs.into_iter().map(|(_, v)| v)).collect(),
+            });
+        }
+        return Supervalue::Vec(SupervalueVec {
+            type_: SupervalueVecType::YamlMap,
+            value: pairs.into_iter().map(|(k, v)| Supervalue::Vec(SupervalueVec {
+                type_: SupervalueVecType::Normal,
+                value: vec![k, v],
+            })).collect(),
+        });
+    }
+
+    /// The entries of a map (including complex key maps and arrays of key/value
+    /// pairs) with the original keys, or `None` if this isn't a map. Map keys are
+    /// sorted.
+    pub fn map_pairs(&self) -> Option<Vec<(Supervalue, &Supervalue)>> {
+        match self {
+            Supervalue::Map(map) => {
+                let mut keys = map.value.keys().collect::<Vec<_>>();
+                keys.sort();
+                return Some(keys.into_iter().map(|k| {
+                    if let SupervalueMapType::ComplexKeys(complex) = &map.type_ {
+                        if complex.contains(k) {
+                            if let Ok(key) = serde_json::from_str::<serde_json::Value>(k) {
+                                return (Supervalue::from(key), &map.value[k]);
+                            }
+                        }
+                    }
+                    return (Supervalue::String(k.clone()), &map.value[k]);
+                }).collect());
+            },
+            Supervalue::Vec(vec) if vec.type_ == SupervalueVecType::YamlMap => {
+                let mut out = vec![];
+                for kv in &vec.value {
+                    let Supervalue::Vec(kv) = kv else {
+                        return None;
+                    };
+                    let [k, v] = kv.value.as_slice() else {
+                        return None;
+                    };
+                    out.push((k.clone(), v));
+                }
+                return Some(out);
+            },
+            _ => return None,
+        }
+    }
+}
+
 impl From<serde_json::Value> for Supervalue {
     fn from(value: serde_json::Value) -> Self {
         match value {
             serde_json::Value::Null => return Self::Null,
             serde_json::Value::Bool(v) => return Self::Bool(v),
-            serde_json::Value::Number(v) => return Self::JsonNumber(v),
+            serde_json::Value::Number(v) => return Self::Number(v.into()),
             serde_json::Value::String(v) => return Self::String(v),
             serde_json::Value::Array(v) => return Self::Vec(SupervalueVec {
                 type_: SupervalueVecType::Normal,
@@ -93,39 +260,56 @@ impl From<serde_json::Value> for Supervalue {
     }
 }
 
-impl Into<serde_json::Value> for Supervalue {
-    fn into(self) -> serde_json::Value {
+impl Supervalue {
+    /// Convert to JSON. Non-finite numbers can't be represented, and are an error
+    /// unless `lossy_numbers` is set (then they become `null`).
+    pub fn to_json_value(self, options: &ConvertOptions) -> Result<serde_json::Value, String> {
         match self {
-            Supervalue::Map(v) => return serde_json::Value::Object(
-                v.value.into_iter().map(|(k, v)| (k, v.into())).collect(),
-            ),
-            Supervalue::Vec(v) => return serde_json::Value::Array(v.value.into_iter().map(|x| x.into()).collect()),
-            Supervalue::Null => return serde_json::Value::Null,
-            Supervalue::Bool(v) => return serde_json::Value::Bool(v),
-            Supervalue::String(v) => return serde_json::Value::String(v),
-            Supervalue::JsonNumber(v) => return serde_json::Value::Number(v),
-            Supervalue::YamlNumber(v) => if v.is_f64() {
-                if let Some(v) = serde_json::Number::from_f64(v.as_f64().unwrap().into()) {
-                    return serde_json::Value::Number(v);
-                } else {
-                    return serde_json::Value::String(v.to_string());
+            Supervalue::Map(v) => {
+                let mut out = serde_json::Map::new();
+                for (k, v) in v.value {
+                    out.insert(k, v.to_json_value(options)?);
                 }
-            } else if v.is_u64() {
-                return serde_json::Value::Number(v.as_u64().unwrap().into());
-            } else {
-                return serde_json::Value::Number(v.as_i64().unwrap().into());
+                return Ok(serde_json::Value::Object(out));
+            },
+            Supervalue::Vec(v) => return Ok(
+                serde_json::Value::Array(
+                    v.value.into_iter().map(|x| x.to_json_value(options)).collect::<Result<_, _>>()?,
+                ),
+            ),
+            Supervalue::Null => return Ok(serde_json::Value::Null),
+            Supervalue::Bool(v) => return Ok(serde_json::Value::Bool(v)),
+            Supervalue::String(v) => return Ok(serde_json::Value::String(v)),
+            Supervalue::Number(v) => match v {
+                SupervalueNumber::Finite(v) => return Ok(serde_json::Value::Number(v)),
+                v => {
+                    if !options.lossy_numbers {
+                        return Err(
+                            format!("Number [{}] can't be represented in JSON (lossy number conversion is disabled)", v),
+                        );
+                    }
+                    return Ok(serde_json::Value::Null);
+                },
             },
-            Supervalue::TomlDatetime(v) => return serde_json::Value::String(v.to_string()),
+            Supervalue::Datetime(v) => return Ok(serde_json::Value::String(v.to_string())),
+            Supervalue::Bytes(v) => return Ok(serde_json::Value::String(BASE64_STANDARD.encode(v))),
         }
     }
 }
 
+impl Into<serde_json::Value> for Supervalue {
+    /// Lossy, see `to_json_value`.
+    fn into(self) -> serde_json::Value {
+        return self.to_json_value(&ConvertOptions::lossy()).unwrap();
+    }
+}
+
 impl From<serde_yaml::Value> for Supervalue {
     fn from(value: serde_yaml::Value) -> Self {
         match value {
             serde_yaml::Value::Null => Supervalue::Null,
             serde_yaml::Value::Bool(v) => Supervalue::Bool(v),
-            serde_yaml::Value::Number(v) => Supervalue::YamlNumber(v),
+            serde_yaml::Value::Number(v) => Supervalue::Number(v.into()),
             serde_yaml::Value::String(v) => Supervalue::String(v),
             serde_yaml::Value::Sequence(v) => return Self::Vec(SupervalueVec {
                 type_: SupervalueVecType::Normal,
@@ -146,53 +330,43 @@ impl From<serde_yaml::Value> for Supervalue {
                             .collect(),
                     });
                 } else {
-                    // Complex map, give up addressing and treat it as opaque array
-                    return Self::Vec(SupervalueVec {
-                        type_: SupervalueVecType::YamlMap,
-                        value: v.into_iter().map(|(k, v)| Supervalue::Vec(SupervalueVec {
-                            type_: SupervalueVecType::Normal,
-                            value: vec![Supervalue::from(k), Supervalue::from(v)],
-                        })).collect(),
-                    });
+                    // Complex map, keys addressed by their JSON serialization
+                    return Self::from_pairs(
+                        v.into_iter().map(|(k, v)| (Supervalue::from(k), Supervalue::from(v))).collect(),
+                    );
                 }
             },
-            serde_yaml::Value::Tagged(v) => return Self::Map(SupervalueMap {
-                type_: SupervalueMapType::YamlTag,
-                value: {
-                    let mut out = HashMap::new();
-                    out.insert(YAML_TAG_TAG.to_string(), Supervalue::String(v.tag.to_string()));
-                    out.insert(YAML_TAG_VALUE.to_string(), Supervalue::from(v.value));
-                    out
-                },
-            }),
+            serde_yaml::Value::Tagged(v) => return Self::Map(
+                SupervalueMap::new_tagged(v.tag.to_string(), Supervalue::from(v.value)),
+            ),
         }
     }
 }
 
-impl Into<serde_yaml::Value> for Supervalue {
-    fn into(self) -> serde_yaml::Value {
+impl Supervalue {
+    /// Convert to YAML. Numbers that don't fit in `u64`/`i64`/`f64` exactly are an
+    /// error unless `lossy_numbers` is set.
+    pub fn to_yaml_value(self, options: &ConvertOptions) -> Result<serde_yaml::Value, String> {
         match self {
             Supervalue::Map(v) => {
-                match v.type_ {
-                    SupervalueMapType::Normal => {
-                        // default result
-                    },
-                    SupervalueMapType::YamlTag => shed!{
-                        let Some(Supervalue::String(tag)) = v.value.get(YAML_TAG_TAG) else {
-                            break;
-                        };
-                        let Some(v) = v.value.get(YAML_TAG_VALUE) else {
-                            break;
-                        };
-                        return serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
-                            tag: serde_yaml::value::Tag::new(tag),
-                            value: v.clone().into(),
-                        }));
-                    },
+                if let Some((tag, v)) = v.as_tagged() {
+                    return Ok(serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+                        tag: serde_yaml::value::Tag::new(tag),
+                        value: v.clone().to_yaml_value(options)?,
+                    })));
                 }
-                return serde_yaml::Value::Mapping(
-                    v.value.into_iter().map(|(k, v)| (serde_yaml::Value::String(k), v.into())).collect(),
-                );
+                if let SupervalueMapType::ComplexKeys(_) = &v.type_ {
+                    let mut out = serde_yaml::Mapping::with_capacity(v.value.len());
+                    for (k, v) in Supervalue::Map(v).map_pairs().unwrap() {
+                        out.insert(k.to_yaml_value(options)?, v.clone().to_yaml_value(options)?);
+                    }
+                    return Ok(serde_yaml::Value::Mapping(out));
+                }
+                let mut out = serde_yaml::Mapping::with_capacity(v.value.len());
+                for (k, v) in v.value {
+                    out.insert(serde_yaml::Value::String(k), v.to_yaml_value(options)?);
+                }
+                return Ok(serde_yaml::Value::Mapping(out));
             },
             Supervalue::Vec(v) => {
                 match v.type_ {
@@ -212,43 +386,46 @@ impl Into<serde_yaml::Value> for Supervalue {
                             if kv.next().is_some() {
                                 break 'bad_map;
                             }
-                            out.insert(k.clone().into(), v.clone().into());
+                            out.insert(k.clone().to_yaml_value(options)?, v.clone().to_yaml_value(options)?);
                         }
-                        return serde_yaml::Value::Mapping(out);
+                        return Ok(serde_yaml::Value::Mapping(out));
                     },
                 }
-                return serde_yaml::Value::Sequence(v.value.into_iter().map(|x| x.into()).collect());
+                return Ok(
+                    serde_yaml::Value::Sequence(
+                        v.value.into_iter().map(|x| x.to_yaml_value(options)).collect::<Result<_, _>>()?,
+                    ),
+                );
             },
-            Supervalue::Null => return serde_yaml::Value::Null,
-            Supervalue::Bool(v) => return serde_yaml::Value::Bool(v),
-            Supervalue::String(v) => return serde_yaml::Value::String(v),
-            Supervalue::YamlNumber(v) => return serde_yaml::Value::Number(v),
-            Supervalue::JsonNumber(v) => if v.is_f64() {
-                return serde_yaml::Value::Number(v.as_f64().unwrap().into());
-            } else if v.is_u64() {
-                return serde_yaml::Value::Number(v.as_u64().unwrap().into());
-            } else {
-                return serde_yaml::Value::Number(v.as_i64().unwrap().into());
+            Supervalue::Null => return Ok(serde_yaml::Value::Null),
+            Supervalue::Bool(v) => return Ok(serde_yaml::Value::Bool(v)),
+            Supervalue::String(v) => return Ok(serde_yaml::Value::String(v)),
+            Supervalue::Number(v) => match v.to_primitive("YAML", true, options.lossy_numbers)? {
+                NumberPrimitive::U64(v) => return Ok(serde_yaml::Value::Number(v.into())),
+                NumberPrimitive::I64(v) => return Ok(serde_yaml::Value::Number(v.into())),
+                NumberPrimitive::F64(v) => return Ok(serde_yaml::Value::Number(v.into())),
             },
-            Supervalue::TomlDatetime(v) => return serde_yaml::Value::String(v.to_string()),
+            Supervalue::Datetime(v) => return Ok(serde_yaml::Value::String(v.to_string())),
+            Supervalue::Bytes(v) => return Ok(serde_yaml::Value::String(BASE64_STANDARD.encode(v))),
         }
     }
 }
 
+impl Into<serde_yaml::Value> for Supervalue {
+    /// Lossy, see `to_yaml_value`.
+    fn into(self) -> serde_yaml::Value {
+        return self.to_yaml_value(&ConvertOptions::lossy()).unwrap();
+    }
+}
+
 impl From<toml::value::Value> for Supervalue {
     fn from(value: toml::value::Value) -> Self {
         match value {
             toml::Value::String(v) => return Self::String(v),
-            toml::Value::Integer(v) => return Self::JsonNumber(serde_json::Number::from(v)),
-            toml::Value::Float(v) => {
-                if let Some(v) = serde_json::Number::from_f64(v) {
-                    return Self::JsonNumber(v);
-                } else {
-                    return Self::String(v.to_string());
-                }
-            },
+            toml::Value::Integer(v) => return Self::Number(v.into()),
+            toml::Value::Float(v) => return Self::Number(v.into()),
             toml::Value::Boolean(v) => return Self::Bool(v),
-            toml::Value::Datetime(v) => return Self::TomlDatetime(v),
+            toml::Value::Datetime(v) => return Self::Datetime(v.into()),
             toml::Value::Array(v) => return Self::Vec(SupervalueVec {
                 type_: SupervalueVecType::Normal,
                 value: v.into_iter().map(|v| v.into()).collect(),
@@ -261,47 +438,441 @@ impl From<toml::value::Value> for Supervalue {
     }
 }
 
+impl Supervalue {
+    /// Convert to TOML. Integers outside the `i64` range and numbers that aren't
+    /// exactly `f64` are an error unless `lossy_numbers` is set, and nulls are
+    /// handled according to `toml_null`.
+    pub fn to_toml_value(self, options: &ConvertOptions) -> Result<toml::value::Value, String> {
+        match self {
+            Supervalue::Map(v) => {
+                let mut out = toml::map::Map::new();
+                for (k, v) in v.value {
+                    if v == Supervalue::Null {
+                        match options.toml_null {
+                            TomlNull::Omit => continue,
+                            TomlNull::Error => return Err(format!("Value at key [{}] is null, which TOML can't represent", k)),
+                            TomlNull::Empty => { },
+                        }
+                    }
+                    out.insert(k, v.to_toml_value(options)?);
+                }
+                return Ok(toml::value::Value::Table(out));
+            },
+            Supervalue::Vec(v) => {
+                let mut out = vec![];
+                for (i, v) in v.value.into_iter().enumerate() {
+                    if v == Supervalue::Null {
+                        match options.toml_null {
+                            TomlNull::Omit => continue,
+                            TomlNull::Error => return Err(
+                                format!("Array element {} is null, which TOML can't represent", i),
+                            ),
+                            TomlNull::Empty => { },
+                        }
+                    }
+                    out.push(v.to_toml_value(options)?);
+                }
+                return Ok(toml::value::Value::Array(out));
+            },
+            Supervalue::Null => match options.toml_null {
+                TomlNull::Empty => return Ok(toml::value::Value::String(String::new())),
+                _ => return Err(format!("The data is null, which TOML can't represent")),
+            },
+            Supervalue::Bool(v) => return Ok(toml::value::Value::Boolean(v)),
+            Supervalue::String(v) => return Ok(toml::value::Value::String(v)),
+            Supervalue::Number(v) => match v.to_primitive("TOML", false, options.lossy_numbers)? {
+                NumberPrimitive::U64(_) => unreachable!(),
+                NumberPrimitive::I64(v) => return Ok(toml::value::Value::Integer(v)),
+                NumberPrimitive::F64(v) => return Ok(toml::value::Value::Float(v)),
+            },
+            Supervalue::Datetime(v) => return Ok(toml::value::Value::Datetime(v.0)),
+            Supervalue::Bytes(v) => return Ok(toml::value::Value::String(BASE64_STANDARD.encode(v))),
+        }
+    }
+}
+
 impl Into<toml::value::Value> for Supervalue {
+    /// Lossy, see `to_toml_value`.
     fn into(self) -> toml::value::Value {
+        return self.to_toml_value(&ConvertOptions::lossy()).unwrap();
+    }
+}
+
+impl From<ron::Value> for Supervalue {
+    fn from(value: ron::Value) -> Self {
+        match value {
+            ron::Value::Bool(v) => return Self::Bool(v),
+            ron::Value::Char(v) => return Self::String(v.to_string()),
+            ron::Value::String(v) => return Self::String(v),
+            ron::Value::Number(v) => match v {
+                ron::Number::I8(v) => return Self::Number((v as i64).into()),
+                ron::Number::I16(v) => return Self::Number((v as i64).into()),
+                ron::Number::I32(v) => return Self::Number((v as i64).into()),
+                ron::Number::I64(v) => return Self::Number(v.into()),
+                ron::Number::U8(v) => return Self::Number((v as u64).into()),
+                ron::Number::U16(v) => return Self::Number((v as u64).into()),
+                ron::Number::U32(v) => return Self::Number((v as u64).into()),
+                ron::Number::U64(v) => return Self::Number(v.into()),
+                v => return Self::Number(v.into_f64().into()),
+            },
+            ron::Value::Option(None) | ron::Value::Unit => return Self::Null,
+            ron::Value::Option(Some(v)) => return Supervalue::from(*v),
+            ron::Value::Bytes(v) => return Self::Bytes(v),
+            ron::Value::Seq(v) => return Self::Vec(SupervalueVec {
+                type_: SupervalueVecType::Normal,
+                value: v.into_iter().map(Supervalue::from).collect(),
+            }),
+            ron::Value::Map(v) => {
+                if v.keys().all(|k| exenum!(k, ron:: Value:: String(_) =>()).is_some()) {
+                    return Self::Map(SupervalueMap {
+                        type_: SupervalueMapType::Normal,
+                        value: v
+                            .into_iter()
+                            .map(|(k, v)| (exenum!(k, ron:: Value:: String(k) => k).unwrap(), Supervalue::from(v)))
+                            .collect(),
+                    });
+                } else {
+                    // Complex map, same representation as complex yaml maps
+                    return Self::from_pairs(
+                        v.into_iter().map(|(k, v)| (Supervalue::from(k), Supervalue::from(v))).collect(),
+                    );
+                }
+            },
+        }
+    }
+}
+
+impl Supervalue {
+    /// Convert to RON. Numbers that don't fit in `u64`/`i64`/`f64` exactly are an
+    /// error unless `lossy_numbers` is set.
+    pub fn to_ron_value(self, options: &ConvertOptions) -> Result<ron::Value, String> {
         match self {
-            Supervalue::Map(v) => return toml::value::Value::Table(
-                v.value.into_iter().map(|(k, v)| (k, v.into())).collect(),
-            ),
-            Supervalue::Vec(v) => return toml::value::Value::Array(
-                v.value.into_iter().map(|x| x.into()).collect(),
-            ),
-            Supervalue::Null => return toml::value::Value::String("null".to_string()),
-            Supervalue::Bool(v) => return toml::value::Value::Boolean(v),
-            Supervalue::String(v) => return toml::value::Value::String(v),
-            Supervalue::JsonNumber(v) => if v.is_f64() {
-                return toml::value::Value::Float(v.as_f64().unwrap());
-            } else if v.is_i64() {
-                return toml::value::Value::Integer(v.as_i64().unwrap());
-            } else {
-                return toml::value::Value::String(v.to_string());
+            Supervalue::Map(v) => {
+                let mut out = ron::Map::new();
+                if let SupervalueMapType::ComplexKeys(_) = &v.type_ {
+                    for (k, v) in Supervalue::Map(v).map_pairs().unwrap() {
+                        out.insert(k.to_ron_value(options)?, v.clone().to_ron_value(options)?);
+                    }
+                    return Ok(ron::Value::Map(out));
+                }
+                for (k, v) in v.value {
+                    out.insert(ron::Value::String(k), v.to_ron_value(options)?);
+                }
+                return Ok(ron::Value::Map(out));
             },
-            Supervalue::YamlNumber(v) => if v.is_f64() {
-                return toml::value::Value::Float(v.as_f64().unwrap());
-            } else if v.is_i64() {
-                return toml::value::Value::Integer(v.as_i64().unwrap());
-            } else {
-                return toml::value::Value::String(v.to_string());
+            Supervalue::Vec(v) => {
+                match v.type_ {
+                    SupervalueVecType::Normal => {
+                        // default result
+                    },
+                    SupervalueVecType::YamlMap => shed!{
+                        'bad_map _;
+                        let mut out = ron::Map::new();
+                        for kv in &v.value {
+                            let Supervalue::Vec(kv) = kv else {
+                                break 'bad_map;
+                            };
+                            let [k, v] = kv.value.as_slice() else {
+                                break 'bad_map;
+                            };
+                            out.insert(k.clone().to_ron_value(options)?, v.clone().to_ron_value(options)?);
+                        }
+                        return Ok(ron::Value::Map(out));
+                    },
+                }
+                return Ok(
+                    ron::Value::Seq(v.value.into_iter().map(|x| x.to_ron_value(options)).collect::<Result<_, _>>()?),
+                );
             },
-            Supervalue::TomlDatetime(v) => return toml::value::Value::Datetime(v),
+            Supervalue::Null => return Ok(ron::Value::Option(None)),
+            Supervalue::Bool(v) => return Ok(ron::Value::Bool(v)),
+            Supervalue::String(v) => return Ok(ron::Value::String(v)),
+            Supervalue::Number(v) => match v.to_primitive("RON", true, options.lossy_numbers)? {
+                NumberPrimitive::U64(v) => return Ok(ron::Value::Number(v.into())),
+                NumberPrimitive::I64(v) => return Ok(ron::Value::Number(v.into())),
+                NumberPrimitive::F64(v) => return Ok(ron::Value::Number(v.into())),
+            },
+            Supervalue::Datetime(v) => return Ok(ron::Value::String(v.to_string())),
+            Supervalue::Bytes(v) => return Ok(ron::Value::Bytes(v)),
         }
     }
 }
 
+impl Into<ron::Value> for Supervalue {
+    /// Lossy, see `to_ron_value`.
+    fn into(self) -> ron::Value {
+        return self.to_ron_value(&ConvertOptions::lossy()).unwrap();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AargSupervalueOriginalFormat {
     Json,
+    Json5,
     Yaml,
     Toml,
+    Ron,
+    Xml,
+    Csv,
+    Tsv,
+    Env,
+    Ini,
+    Properties,
+    Cbor,
+    Msgpack,
 }
 
 pub struct AargSupervalue {
     pub original_format: AargSupervalueOriginalFormat,
     pub value: Supervalue,
     pub source: aargvark::traits_impls::Source,
+    /// A schema declared in a comment directive (`# yaml-language-server:
+    /// $schema=...` for YAML, `#:schema ...` for TOML), if any.
+    pub schema_directive: Option<String>,
+    /// Anchors in YAML data, for writing them back out.
+    pub yaml_anchors: Vec<YamlAnchor>,
+}
+
+/// Find a `# yaml-language-server: $schema=...` comment anywhere in the YAML
+/// document, ignoring lines in block scalars.
+pub fn yaml_schema_directive(text: &str) -> Option<String> {
+    let scalar_lines = yaml_scalar_lines(text).unwrap_or_default();
+    for (i, line) in text.lines().enumerate() {
+        if scalar_lines.contains(&i) {
+            continue;
+        }
+        let Some(comment) = line.trim_start().strip_prefix("#") else {
+            continue;
+        };
+        let Some(directive) = comment.trim_start().strip_prefix("yaml-language-server:") else {
+            continue;
+        };
+        let Some(addr) = directive.trim_start().strip_prefix("$schema=") else {
+            continue;
+        };
+        let addr = addr.trim();
+        if addr.is_empty() {
+            continue;
+        }
+        return Some(addr.to_string());
+    }
+    return None;
+}
+
+/// The anchors in a YAML document. Anchors are only used for output, so if they
+/// can't be determined they're ignored.
+fn yaml_anchors(text: &str, value: &Supervalue) -> Vec<YamlAnchor> {
+    return find_yaml_anchors(text, value).unwrap_or_default();
+}
+
+/// Find a `#:schema ...` comment in the comment header (before any
+/// non-comment content) of the TOML document.
+pub fn toml_schema_directive(text: &str) -> Option<String> {
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix("#") else {
+            break;
+        };
+        let Some(addr) = comment.strip_prefix(":schema") else {
+            continue;
+        };
+        let addr = addr.trim();
+        if addr.is_empty() {
+            continue;
+        }
+        return Some(addr.to_string());
+    }
+    return None;
+}
+
+impl AargSupervalueOriginalFormat {
+    /// Guess the format from a file extension, case insensitive.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "json" | "jsonc" | "geojson" => return Some(Self::Json),
+            "json5" => return Some(Self::Json5),
+            "yaml" | "yml" => return Some(Self::Yaml),
+            "toml" => return Some(Self::Toml),
+            "ron" => return Some(Self::Ron),
+            "xml" | "pom" | "xsd" | "svg" => return Some(Self::Xml),
+            "csv" => return Some(Self::Csv),
+            "tsv" | "tab" => return Some(Self::Tsv),
+            "env" => return Some(Self::Env),
+            "ini" | "cfg" => return Some(Self::Ini),
+            "properties" => return Some(Self::Properties),
+            "cbor" => return Some(Self::Cbor),
+            "msgpack" | "mpk" => return Some(Self::Msgpack),
+            _ => return None,
+        }
+    }
+}
+
+/// Parse JSON with comments and trailing commas. Plain JSON is parsed directly
+/// since the comment stripping mangles some valid JSON, like `[{}, {}]`.
+fn from_jsonc_str(text: &str) -> Result<serde_json::Value, serde_json::Error> {
+    return serde_json::from_str(text).or_else(|_| serde_json::from_str(&jsonc_to_json(text)));
+}
+
+/// Parse `text` in the given format, returning the value and any schema comment
+/// directive.
+pub fn parse_format(
+    format: AargSupervalueOriginalFormat,
+    text: &str,
+) -> Result<(Supervalue, Option<String>), String> {
+    match format {
+        AargSupervalueOriginalFormat::Json => {
+            let data = from_jsonc_str(text).map_err(|e| e.to_string())?;
+            return Ok((data.into(), None));
+        },
+        AargSupervalueOriginalFormat::Json5 => {
+            return Ok((json5::from_str::<Supervalue>(text).map_err(|e| e.to_string())?, None));
+        },
+        AargSupervalueOriginalFormat::Yaml => {
+            let data = serde_yaml::from_str::<serde_yaml::Value>(text).map_err(|e| e.to_string())?;
+            return Ok((data.into(), yaml_schema_directive(text)));
+        },
+        AargSupervalueOriginalFormat::Toml => {
+            let data = toml::from_str::<toml::Value>(text).map_err(|e| e.to_string())?;
+            return Ok((data.into(), toml_schema_directive(text)));
+        },
+        AargSupervalueOriginalFormat::Ron => {
+            let data = ron::from_str::<ron::Value>(text).map_err(|e| e.to_string())?;
+            return Ok((data.into(), None));
+        },
+        AargSupervalueOriginalFormat::Xml => {
+            return Ok((from_xml_str(text)?, None));
+        },
+        AargSupervalueOriginalFormat::Csv => {
+            return Ok((from_csv_str(text, b',')?, None));
+        },
+        AargSupervalueOriginalFormat::Tsv => {
+            return Ok((from_csv_str(text, b'\t')?, None));
+        },
+        AargSupervalueOriginalFormat::Env => {
+            return Ok((from_env_str(text)?, None));
+        },
+        AargSupervalueOriginalFormat::Ini => {
+            return Ok((from_ini_str(text)?, None));
+        },
+        AargSupervalueOriginalFormat::Properties => {
+            return Ok((from_properties_str(text)?, None));
+        },
+        AargSupervalueOriginalFormat::Cbor => {
+            return Ok((from_cbor_slice(text.as_bytes())?, None));
+        },
+        AargSupervalueOriginalFormat::Msgpack => {
+            return Ok((from_msgpack_slice(text.as_bytes())?, None));
+        },
+    }
+}
+
+/// Like `parse_format` but for raw file contents, which are only required to be
+/// utf-8 for text formats.
+pub fn parse_format_bytes(
+    format: AargSupervalueOriginalFormat,
+    data: &[u8],
+) -> Result<(Supervalue, Option<String>), String> {
+    match format {
+        AargSupervalueOriginalFormat::Cbor => {
+            return Ok((from_cbor_slice(data)?, None));
+        },
+        AargSupervalueOriginalFormat::Msgpack => {
+            return Ok((from_msgpack_slice(data)?, None));
+        },
+        format => {
+            let text = std::str::from_utf8(data).map_err(|e| format!("Invalid utf-8: {}", e))?;
+            return parse_format(format, text);
+        },
+    }
+}
+
+/// Guess the format of `text` by trying JSON (with comments), JSON5, TOML, XML,
+/// then YAML in order, returning the first that parses.
+pub fn sniff_format(text: &str) -> Result<(AargSupervalueOriginalFormat, Supervalue, Option<String>), String> {
+    let mut errors = vec![];
+    for format in [
+        AargSupervalueOriginalFormat::Json,
+        AargSupervalueOriginalFormat::Json5,
+        AargSupervalueOriginalFormat::Toml,
+        AargSupervalueOriginalFormat::Xml,
+        AargSupervalueOriginalFormat::Yaml,
+    ] {
+        match parse_format(format, text) {
+            Ok((value, directive)) => return Ok((format, value, directive)),
+            Err(e) => errors.push(format!("- As {:?}: {}", format, e)),
+        }
+    }
+    return Err(format!("Couldn't detect format, parsing failed for all candidates:\n{}", errors.join("\n")));
+}
+
+/// Read the file at `path` (or stdin if `-`), detecting the format from the
+/// extension or if that's not possible the content.
+fn from_auto_path(path: &str) -> Result<AargSupervalue, String> {
+    let t = AargvarkFile::from_str(path)?;
+    let ext_format = match &t.source {
+        aargvark::traits_impls::Source::Stdin => None,
+        aargvark::traits_impls::Source::File(p) => {
+            if p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n == ".env" || n.starts_with(".env.")) {
+                Some(AargSupervalueOriginalFormat::Env)
+            } else {
+                p.extension().and_then(|e| e.to_str()).and_then(AargSupervalueOriginalFormat::from_extension)
+            }
+        },
+    };
+    let (format, value, directive) = match ext_format {
+        Some(format) => {
+            let (value, directive) =
+                parse_format_bytes(
+                    format,
+                    &t.value,
+                ).map_err(|e| format!("{:?} in file [{}] is invalid: {}", format, path, e))?;
+            (format, value, directive)
+        },
+        None => {
+            let text =
+                std::str::from_utf8(&t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            sniff_format(text).map_err(|e| format!("Error reading file [{}]: {}", path, e))?
+        },
+    };
+    let yaml_anchors = match format {
+        AargSupervalueOriginalFormat::Yaml => yaml_anchors(std::str::from_utf8(&t.value).unwrap_or_default(), &value),
+        _ => vec![],
+    };
+    return Ok(AargSupervalue {
+        original_format: format,
+        value: value,
+        source: t.source,
+        schema_directive: directive,
+        yaml_anchors: yaml_anchors,
+    });
+}
+
+/// Prefixes of source arguments that refer to a file (or `-` for stdin).
+pub const FILE_SOURCE_PREFIXES: &[&str] = &[
+    "fs:",
+    "a:",
+    "f:",
+    "fy:",
+    "ft:",
+    "fj5:",
+    "fron:",
+    "fx:",
+    "fcsv:",
+    "ftsv:",
+    "fenv:",
+    "fini:",
+    "fprops:",
+    "fcbor:",
+    "fmsgpack:",
+];
+
+/// The path in a source argument that refers to a file, like `./a.json` in
+/// `f:./a.json`.
+pub fn file_source_path(s: &str) -> Option<&str> {
+    return FILE_SOURCE_PREFIXES.iter().find_map(|prefix| s.strip_prefix(prefix));
 }
 
 impl AargvarkFromStr for AargSupervalue {
@@ -311,6 +882,20 @@ impl AargvarkFromStr for AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Json,
                 value: Supervalue::String(text.into()),
                 source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some(text) = s.strip_prefix("dt:") {
+            let data =
+                SupervalueDatetime::parse(
+                    text,
+                ).ok_or_else(|| format!("Inline datetime [{}] isn't in RFC 3339 or YAML timestamp format", text))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Json,
+                value: Supervalue::Datetime(data),
+                source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
         } else if let Some(path) = s.strip_prefix("fs:") {
             let t = AargvarkFile::from_str(path)?;
@@ -319,37 +904,116 @@ impl AargvarkFromStr for AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Json,
                 value: Supervalue::String(text),
                 source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
+        } else if let Some(path) = s.strip_prefix("a:") {
+            return from_auto_path(path);
         } else if let Some(path) = s.strip_prefix("f:") {
             let t = AargvarkJson::<serde_json::Value>::from_str(&jsonc_to_json(path))?;
             return Ok(AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Json,
                 value: t.value.into(),
                 source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
         } else if let Some(path) = s.strip_prefix("fy:") {
-            let t = AargvarkYaml::<serde_yaml::Value>::from_str(path)?;
+            let t = AargvarkFile::from_str(path)?;
+            let text = String::from_utf8(t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            let data =
+                serde_yaml::from_str::<serde_yaml::Value>(
+                    &text,
+                ).map_err(|e| format!("YAML in file [{}] is invalid: {}", path, e))?;
+            let data = Supervalue::from(data);
             return Ok(AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Yaml,
-                value: t.value.into(),
+                yaml_anchors: yaml_anchors(&text, &data),
+                value: data,
                 source: t.source,
+                schema_directive: yaml_schema_directive(&text),
             });
         } else if let Some(path) = s.strip_prefix("ft:") {
-            let t = AargvarkToml::<toml::Value>::from_str(path)?;
+            let t = AargvarkFile::from_str(path)?;
+            let text = String::from_utf8(t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            let data =
+                toml::from_str::<toml::Value>(&text).map_err(|e| format!("TOML in file [{}] is invalid: {}", path, e))?;
             return Ok(AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Toml,
-                value: t.value.into(),
+                value: data.into(),
                 source: t.source,
+                schema_directive: toml_schema_directive(&text),
+                yaml_anchors: vec![],
+            });
+        } else if let Some(path) = s.strip_prefix("fj5:") {
+            let t = AargvarkFile::from_str(path)?;
+            let text = String::from_utf8(t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            let data =
+                json5::from_str::<Supervalue>(&text).map_err(|e| format!("JSON5 in file [{}] is invalid: {}", path, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Json5,
+                value: data,
+                source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some(path) = s.strip_prefix("fron:") {
+            let t = AargvarkFile::from_str(path)?;
+            let text = String::from_utf8(t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            let data =
+                ron::from_str::<ron::Value>(&text).map_err(|e| format!("RON in file [{}] is invalid: {}", path, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Ron,
+                value: data.into(),
+                source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some(path) = s.strip_prefix("fx:") {
+            let t = AargvarkFile::from_str(path)?;
+            let text = String::from_utf8(t.value).map_err(|e| format!("Invalid utf-8 in file [{}]: {}", path, e))?;
+            let data = from_xml_str(&text).map_err(|e| format!("XML in file [{}] is invalid: {}", path, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Xml,
+                value: data,
+                source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some((format, path)) =
+            [
+                ("fcsv:", AargSupervalueOriginalFormat::Csv),
+                ("ftsv:", AargSupervalueOriginalFormat::Tsv),
+                ("fenv:", AargSupervalueOriginalFormat::Env),
+                ("fini:", AargSupervalueOriginalFormat::Ini),
+                ("fprops:", AargSupervalueOriginalFormat::Properties),
+                ("fcbor:", AargSupervalueOriginalFormat::Cbor),
+                ("fmsgpack:", AargSupervalueOriginalFormat::Msgpack),
+            ]
+                .into_iter()
+                .find_map(|(prefix, format)| s.strip_prefix(prefix).map(|p| (format, p))) {
+            let t = AargvarkFile::from_str(path)?;
+            let (data, _) =
+                parse_format_bytes(format, &t.value).map_err(|e| format!("{:?} in file [{}] is invalid: {}", format, path, e))?;
+            return Ok(AargSupervalue {
+                original_format: format,
+                value: data,
+                source: t.source,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
         } else if let Some(v) = s.strip_prefix("y:") {
             let data =
                 serde_yaml::from_str::<serde_yaml::Value>(
                     &v,
                 ).map_err(|e| format!("Inline yaml [{}] is invalid: {}", v, e))?;
+            let data = Supervalue::from(data);
             return Ok(AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Yaml,
-                value: data.into(),
+                yaml_anchors: yaml_anchors(v, &data),
+                value: data,
                 source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: yaml_schema_directive(v),
             });
         } else if let Some(v) = s.strip_prefix("t:") {
             let data =
@@ -358,16 +1022,71 @@ impl AargvarkFromStr for AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Toml,
                 value: data.into(),
                 source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: toml_schema_directive(v),
+                yaml_anchors: vec![],
+            });
+        } else if let Some(v) = s.strip_prefix("j5:") {
+            let data = json5::from_str::<Supervalue>(&v).map_err(|e| format!("Inline json5 [{}] is invalid: {}", v, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Json5,
+                value: data,
+                source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some(v) = s.strip_prefix("x:") {
+            let data = from_xml_str(v).map_err(|e| format!("Inline xml [{}] is invalid: {}", v, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Xml,
+                value: data,
+                source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some((format, v)) =
+            [
+                ("csv:", AargSupervalueOriginalFormat::Csv),
+                ("tsv:", AargSupervalueOriginalFormat::Tsv),
+                ("env:", AargSupervalueOriginalFormat::Env),
+                ("ini:", AargSupervalueOriginalFormat::Ini),
+                ("props:", AargSupervalueOriginalFormat::Properties),
+            ]
+                .into_iter()
+                .find_map(|(prefix, format)| s.strip_prefix(prefix).map(|v| (format, v))) {
+            let (data, _) = parse_format(format, v).map_err(|e| format!("Inline {:?} [{}] is invalid: {}", format, v, e))?;
+            return Ok(AargSupervalue {
+                original_format: format,
+                value: data,
+                source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
+            });
+        } else if let Some(v) = s.strip_prefix("ron:") {
+            let data = ron::from_str::<ron::Value>(&v).map_err(|e| format!("Inline ron [{}] is invalid: {}", v, e))?;
+            return Ok(AargSupervalue {
+                original_format: AargSupervalueOriginalFormat::Ron,
+                value: data.into(),
+                source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
         } else {
-            let data =
-                serde_json::from_str::<serde_json::Value>(
-                    &jsonc_to_json(s),
-                ).map_err(|e| format!("Inline json [{}] is invalid: {}", s, e))?;
+            let data = match from_jsonc_str(s) {
+                Ok(data) => data,
+                Err(e) => {
+                    // Bare path
+                    if s == "-" || Path::new(s).is_file() {
+                        return from_auto_path(s);
+                    }
+                    return Err(format!("Inline json [{}] is invalid: {}", s, e));
+                },
+            };
             return Ok(AargSupervalue {
                 original_format: AargSupervalueOriginalFormat::Json,
                 value: data.into(),
                 source: aargvark::traits_impls::Source::Stdin,
+                schema_directive: None,
+                yaml_anchors: vec![],
             });
         }
     }
@@ -376,3 +1095,190 @@ impl AargvarkFromStr for AargSupervalue {
         return aargvark::help::HelpPattern(vec![aargvark::help::HelpPatternElement::Type("VALUE".to_string())]);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_jsonc_str,
+            sniff_format,
+            toml_schema_directive,
+            yaml_schema_directive,
+            AargSupervalueOriginalFormat,
+            ConvertOptions,
+            Supervalue,
+            SupervalueVec,
+            SupervalueVecType,
+            TomlNull,
+        },
+        crate::{
+            set::set,
+            supervalue_number::SupervalueNumber,
+            supervalue_path::DataPath,
+        },
+        serde_json::json,
+    };
+
+    #[test]
+    fn jsonc() {
+        assert_eq!(from_jsonc_str(r#"[{"a": 1}, {"b": 2}]"#).unwrap(), json!([{ "a": 1 }, { "b": 2 }]));
+        assert_eq!(from_jsonc_str("[1, // one\n 2,]").unwrap(), json!([1, 2]));
+    }
+
+    #[test]
+    fn yaml_directive() {
+        assert_eq!(
+            yaml_schema_directive("a: 1\n# yaml-language-server: $schema=./schema.json\nb: 2\n"),
+            Some("./schema.json".to_string())
+        );
+        assert_eq!(yaml_schema_directive("# $schema=./schema.json\na: 1\n"), None);
+        assert_eq!(
+            yaml_schema_directive(
+                "a: |\n  # yaml-language-server: $schema=./a.json\n  b\n# yaml-language-server: $schema=./b.json\n",
+            ),
+            Some("./b.json".to_string())
+        );
+        assert_eq!(yaml_schema_directive("a: >-\n  # yaml-language-server: $schema=./a.json\nb: 1\n"), None);
+    }
+
+    #[test]
+    fn toml_directive() {
+        assert_eq!(
+            toml_schema_directive("# Config\n#:schema https://example.com/schema.json\n\na = 1\n"),
+            Some("https://example.com/schema.json".to_string())
+        );
+        assert_eq!(toml_schema_directive("a = 1\n#:schema ./schema.json\n"), None);
+    }
+
+    #[test]
+    fn ron() {
+        let source = Supervalue::from(ron::from_str::<ron::Value>("Config(name: \"x\", port: Some(80), tags: [])").unwrap());
+        assert_eq!(source, Supervalue::from(json!({
+            "name": "x",
+            "port": 80,
+            "tags": [],
+        })));
+        let text = ron::to_string(&<Supervalue as Into<ron::Value>>::into(source.clone())).unwrap();
+        assert_eq!(Supervalue::from(ron::from_str::<ron::Value>(&text).unwrap()), source);
+    }
+
+    #[test]
+    fn yaml_tags() {
+        let text = "a: !Ref foo\nb: !Sub\n- x\n- y: 1\nc: !!str 1\n";
+        let source = Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>(text).unwrap());
+        let Supervalue::Map(m) = &source else {
+            unreachable!();
+        };
+        let Supervalue::Map(a) = &m.value["a"] else {
+            unreachable!();
+        };
+        assert_eq!(a.as_tagged(), Some(("!Ref", &Supervalue::String("foo".to_string()))));
+        let round_trip =
+            Supervalue::from(
+                serde_yaml::from_str::<serde_yaml::Value>(
+                    &serde_yaml::to_string(&<Supervalue as Into<serde_yaml::Value>>::into(source.clone())).unwrap(),
+                ).unwrap(),
+            );
+        assert_eq!(round_trip, source);
+        let round_trip =
+            serde_yaml::from_str::<Supervalue>(&serde_yaml::to_string(&source).unwrap()).unwrap();
+        assert_eq!(round_trip, source);
+    }
+
+    #[test]
+    fn complex_keys() {
+        let text = "1: one\n[1, 2]: pair\n\"1.5\": x\n";
+        let mut source = Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>(text).unwrap());
+        assert_eq!(<Supervalue as Into<serde_json::Value>>::into(source.clone()), json!({
+            "1": "one",
+            "[1,2]": "pair",
+            "1.5": "x"
+        }));
+        set(&mut source, &DataPath(vec![json!("[1,2]")]), &Supervalue::String("changed".to_string()), false).unwrap();
+        let round_trip =
+            serde_yaml::from_str::<serde_yaml::Value>(
+                &serde_yaml::to_string(&<Supervalue as Into<serde_yaml::Value>>::into(source.clone())).unwrap(),
+            ).unwrap();
+        assert_eq!(round_trip, serde_yaml::from_str::<serde_yaml::Value>("1: one\n[1, 2]: changed\n\"1.5\": x\n").unwrap());
+
+        // Keys that serialize the same can't be addressed
+        let source = Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>("1: a\n\"1\": b\n").unwrap());
+        assert!(matches!(source, Supervalue::Vec(SupervalueVec { type_: SupervalueVecType::YamlMap, .. })));
+    }
+
+    #[test]
+    fn toml_null() {
+        let source = Supervalue::from(json!({
+            "a": null,
+            "b": [1, null]
+        }));
+        let convert = |toml_null| source.clone().to_toml_value(&ConvertOptions {
+            lossy_numbers: false,
+            toml_null: toml_null,
+        });
+        assert_eq!(convert(TomlNull::Omit).unwrap(), toml::from_str::<toml::Value>("b = [1]").unwrap());
+        assert!(convert(TomlNull::Error).is_err());
+        assert_eq!(convert(TomlNull::Empty).unwrap(), toml::from_str::<toml::Value>("a = \"\"\nb = [1, \"\"]").unwrap());
+    }
+
+    #[test]
+    fn numbers() {
+        let exact = ConvertOptions::default();
+        let lossy = ConvertOptions::lossy();
+        let json = |text: &str| Supervalue::from(serde_json::from_str::<serde_json::Value>(text).unwrap());
+
+        // u64 above i64::MAX
+        let big = json("18446744073709551615");
+        assert_eq!(big.clone().to_yaml_value(&exact).unwrap(), serde_yaml::Value::Number(u64::MAX.into()));
+        assert!(big.clone().to_toml_value(&exact).is_err());
+        assert_eq!(big.clone().to_toml_value(&lossy).unwrap(), toml::Value::Float(18446744073709551615.));
+
+        // Beyond 64 bits
+        let huge = json("18446744073709551616");
+        assert!(huge.clone().to_yaml_value(&exact).is_err());
+        assert!(huge.clone().to_ron_value(&exact).is_err());
+        assert_eq!(huge.clone().to_json_value(&exact).unwrap().to_string(), "18446744073709551616");
+
+        // Floats, exact and not
+        let tenth = json("0.1");
+        assert_eq!(tenth.clone().to_toml_value(&exact).unwrap(), toml::Value::Float(0.1));
+        assert_eq!(Supervalue::from(tenth.clone().to_yaml_value(&exact).unwrap()), tenth);
+        let precise = json("0.10000000000000000001");
+        assert!(precise.clone().to_yaml_value(&exact).is_err());
+        assert!(precise.clone().to_toml_value(&exact).is_err());
+        assert_eq!(precise.clone().to_yaml_value(&lossy).unwrap(), serde_yaml::Value::Number(0.1.into()));
+
+        // Non-finite
+        let toml_nan = Supervalue::from(toml::from_str::<toml::Value>("a = nan\nb = -inf\n").unwrap());
+        let Supervalue::Map(m) = &toml_nan else {
+            unreachable!();
+        };
+        assert_eq!(m.value["a"], Supervalue::Number(SupervalueNumber::Nan));
+        assert_eq!(m.value["b"], Supervalue::Number(SupervalueNumber::NegInfinity));
+        assert!(toml_nan.clone().to_json_value(&exact).is_err());
+        assert_eq!(toml_nan.clone().to_json_value(&lossy).unwrap(), json!({
+            "a": null,
+            "b": null
+        }));
+        assert_eq!(
+            Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>(
+                &serde_yaml::to_string(&toml_nan.clone().to_yaml_value(&exact).unwrap()).unwrap(),
+            ).unwrap()),
+            toml_nan
+        );
+
+        // Equal by value regardless of source format
+        assert_eq!(Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>("1.0").unwrap()), json("1"));
+        assert_eq!(json("1e3"), json("1000"));
+    }
+
+    #[test]
+    fn sniff() {
+        assert_eq!(sniff_format("// c\n{\"a\": 1}").unwrap().0, AargSupervalueOriginalFormat::Json);
+        assert_eq!(sniff_format("{a: 1}").unwrap().0, AargSupervalueOriginalFormat::Json5);
+        assert_eq!(sniff_format("[x]\na = 1\n").unwrap().0, AargSupervalueOriginalFormat::Toml);
+        let (format, value, _) = sniff_format("a:\n  - 1\n").unwrap();
+        assert_eq!(format, AargSupervalueOriginalFormat::Yaml);
+        assert_eq!(value, Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>("a: [1]").unwrap()));
+    }
+}
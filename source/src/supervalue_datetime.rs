@@ -0,0 +1,191 @@
+use {
+    std::fmt::Display,
+};
+
+/// A date, time, or date and time with an optional offset, from TOML, CBOR
+/// date/time tags, or (with `infer-datetimes`) RFC 3339 and YAML timestamp
+/// strings.
+///
+/// Datetimes with an offset are equal if they're the same instant, so
+/// `2001-12-15T02:59:43Z` and `2001-12-14T21:59:43-05:00` are equal. Others are
+/// equal if they have the same date and time.
+#[derive(Debug, Clone)]
+pub struct SupervalueDatetime(pub toml::value::Datetime);
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 {
+        year - 1
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    return era * 146097 + day_of_era - 719468;
+}
+
+/// Take between `min` and `max` leading ascii digits from `text`.
+fn take_digits<'a>(text: &mut &'a str, min: usize, max: usize) -> Option<&'a str> {
+    let len = text.bytes().take(max).take_while(|c| c.is_ascii_digit()).count();
+    if len < min {
+        return None;
+    }
+    let (digits, rest) = text.split_at(len);
+    *text = rest;
+    return Some(digits);
+}
+
+/// Rewrite a YAML timestamp (which allows single digit months, days and hours,
+/// space separators, and short offsets like `-5`) as RFC 3339.
+fn yaml_timestamp_to_rfc3339(text: &str) -> Option<String> {
+    let mut rest = text;
+    let year = take_digits(&mut rest, 4, 4)?;
+    rest = rest.strip_prefix("-")?;
+    let month = take_digits(&mut rest, 1, 2)?;
+    rest = rest.strip_prefix("-")?;
+    let day = take_digits(&mut rest, 1, 2)?;
+    let mut out = format!("{}-{:0>2}-{:0>2}", year, month, day);
+    if rest.is_empty() {
+        return Some(out);
+    }
+    rest = match rest.strip_prefix(['T', 't']) {
+        Some(r) => r,
+        None => {
+            let r = rest.trim_start_matches([' ', '\t']);
+            if r.len() == rest.len() {
+                return None;
+            }
+            r
+        },
+    };
+    let hour = take_digits(&mut rest, 1, 2)?;
+    rest = rest.strip_prefix(":")?;
+    let minute = take_digits(&mut rest, 2, 2)?;
+    rest = rest.strip_prefix(":")?;
+    let second = take_digits(&mut rest, 2, 2)?;
+    out.push_str(&format!("T{:0>2}:{}:{}", hour, minute, second));
+    if let Some(r) = rest.strip_prefix(".") {
+        rest = r;
+        let fraction = take_digits(&mut rest, 0, usize::MAX)?;
+        if !fraction.is_empty() {
+            out.push_str(&format!(".{}", fraction));
+        }
+    }
+    rest = rest.trim_start_matches([' ', '\t']);
+    if rest.is_empty() {
+        return Some(out);
+    }
+    if rest == "Z" || rest == "z" {
+        out.push('Z');
+        return Some(out);
+    }
+    let sign = if rest.starts_with("+") {
+        "+"
+    } else if rest.starts_with("-") {
+        "-"
+    } else {
+        return None;
+    };
+    rest = &rest[1..];
+    let offset_hour = take_digits(&mut rest, 1, 2)?;
+    let offset_minute = match rest.strip_prefix(":") {
+        Some(mut r) => {
+            let m = take_digits(&mut r, 2, 2)?;
+            rest = r;
+            m
+        },
+        None => "00",
+    };
+    if !rest.is_empty() {
+        return None;
+    }
+    out.push_str(&format!("{}{:0>2}:{}", sign, offset_hour, offset_minute));
+    return Some(out);
+}
+
+impl SupervalueDatetime {
+    /// Parse an RFC 3339 or YAML timestamp string with a date (and optionally a
+    /// time and offset).
+    pub fn parse(text: &str) -> Option<SupervalueDatetime> {
+        let rfc3339 = yaml_timestamp_to_rfc3339(text)?;
+        return rfc3339.parse::<toml::value::Datetime>().ok().map(SupervalueDatetime);
+    }
+
+    /// Seconds and nanoseconds since the Unix epoch, if this has a date, time and
+    /// offset.
+    pub fn instant(&self) -> Option<(i64, u32)> {
+        let (Some(date), Some(time), Some(offset)) = (&self.0.date, &self.0.time, &self.0.offset) else {
+            return None;
+        };
+        let offset_minutes = match offset {
+            toml::value::Offset::Z => 0,
+            toml::value::Offset::Custom { minutes } => *minutes as i64,
+        };
+        let days = days_from_civil(date.year as i64, date.month as i64, date.day as i64);
+        let seconds =
+            days * 86400 + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64 -
+                offset_minutes * 60;
+        return Some((seconds, time.nanosecond));
+    }
+}
+
+impl PartialEq for SupervalueDatetime {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.instant(), other.instant()) {
+            (Some(a), Some(b)) => return a == b,
+            (None, None) => return self.0 == other.0,
+            _ => return false,
+        }
+    }
+}
+
+impl Eq for SupervalueDatetime { }
+
+impl Display for SupervalueDatetime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.0.fmt(f);
+    }
+}
+
+impl From<toml::value::Datetime> for SupervalueDatetime {
+    fn from(value: toml::value::Datetime) -> Self {
+        return SupervalueDatetime(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::SupervalueDatetime,
+    };
+
+    fn d(text: &str) -> SupervalueDatetime {
+        return SupervalueDatetime::parse(text).unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(d("2001-12-14").to_string(), "2001-12-14");
+        assert_eq!(d("2001-1-2").to_string(), "2001-01-02");
+        assert_eq!(d("2001-12-14t21:59:43.10-05:00").to_string(), "2001-12-14T21:59:43.1-05:00");
+        assert_eq!(d("2001-12-14 21:59:43.10 -5").to_string(), "2001-12-14T21:59:43.1-05:00");
+        assert_eq!(d("2001-12-14 1:59:43").to_string(), "2001-12-14T01:59:43");
+        assert_eq!(d("2001-12-15T02:59:43.1Z").to_string(), "2001-12-15T02:59:43.1Z");
+        assert!(SupervalueDatetime::parse("21:59:43").is_none());
+        assert!(SupervalueDatetime::parse("2001-12-14x").is_none());
+        assert!(SupervalueDatetime::parse("2001-12-14 21:59").is_none());
+        assert!(SupervalueDatetime::parse("2001-13-14").is_none());
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(d("2001-12-14 21:59:43.10 -5"), d("2001-12-15T02:59:43.1Z"));
+        assert_eq!(d("2001-12-15T02:59:43+00:00"), d("2001-12-15t02:59:43z"));
+        assert_eq!(d("1969-12-31T23:00:00-01:00"), d("1970-01-01T00:00:00Z"));
+        assert_eq!(d("2001-12-14T01:00:00"), d("2001-12-14 1:00:00.000"));
+        assert_ne!(d("2001-12-14T01:00:00"), d("2001-12-14T01:00:00Z"));
+        assert_ne!(d("2001-12-14"), d("2001-12-15"));
+    }
+}
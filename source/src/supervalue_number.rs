@@ -0,0 +1,279 @@
+use {
+    std::fmt::Display,
+};
+
+/// A number from any format. Finite numbers keep their exact decimal text
+/// (arbitrary precision), non-finite floats (from YAML, TOML, RON, etc.) are
+/// kept separately since JSON can't represent them.
+///
+/// Numbers are equal if they have the same value, so `1`, `1.0` and `1e0` are
+/// all equal. `NaN` is equal to itself.
+#[derive(Debug, Clone)]
+pub enum SupervalueNumber {
+    Finite(serde_json::Number),
+    Nan,
+    Infinity,
+    NegInfinity,
+}
+
+/// A number in one of the primitive types most formats support.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberPrimitive {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+/// The sign, significant digits (without leading or trailing zeros) and exponent
+/// of a decimal number, for comparing by value. `None` if the exponent is too
+/// large to represent.
+fn normalize_decimal(text: &str) -> Option<(bool, String, i128)> {
+    let (negative, text) = match text.strip_prefix("-") {
+        Some(t) => (true, t),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<i128>().ok()?),
+        None => (text, 0),
+    };
+    let (int, frac) = mantissa.split_once(".").unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+    let digits = digits.trim_start_matches('0');
+    let trimmed = digits.trim_end_matches('0');
+    if trimmed.is_empty() {
+        return Some((false, String::new(), 0));
+    }
+    let exponent =
+        exponent.checked_sub(frac.len() as i128)?.checked_add((digits.len() - trimmed.len()) as i128)?;
+    return Some((negative, trimmed.to_string(), exponent));
+}
+
+/// Compare decimal number text by value. Numbers with unrepresentable exponents
+/// are only equal if the text is identical.
+fn decimal_eq(a: &str, b: &str) -> bool {
+    if let (Some(a), Some(b)) = (normalize_decimal(a), normalize_decimal(b)) {
+        return a == b;
+    }
+    return a == b;
+}
+
+impl PartialEq for SupervalueNumber {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SupervalueNumber::Finite(a), SupervalueNumber::Finite(b)) => {
+                return decimal_eq(&a.to_string(), &b.to_string());
+            },
+            (SupervalueNumber::Nan, SupervalueNumber::Nan) => return true,
+            (SupervalueNumber::Infinity, SupervalueNumber::Infinity) => return true,
+            (SupervalueNumber::NegInfinity, SupervalueNumber::NegInfinity) => return true,
+            _ => return false,
+        }
+    }
+}
+
+impl Eq for SupervalueNumber { }
+
+impl Display for SupervalueNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SupervalueNumber::Finite(v) => return v.fmt(f),
+            SupervalueNumber::Nan => return f.write_str("NaN"),
+            SupervalueNumber::Infinity => return f.write_str("Infinity"),
+            SupervalueNumber::NegInfinity => return f.write_str("-Infinity"),
+        }
+    }
+}
+
+impl From<serde_json::Number> for SupervalueNumber {
+    fn from(value: serde_json::Number) -> Self {
+        return SupervalueNumber::Finite(value);
+    }
+}
+
+impl From<u64> for SupervalueNumber {
+    fn from(value: u64) -> Self {
+        return SupervalueNumber::Finite(value.into());
+    }
+}
+
+impl From<i64> for SupervalueNumber {
+    fn from(value: i64) -> Self {
+        return SupervalueNumber::Finite(value.into());
+    }
+}
+
+impl From<f64> for SupervalueNumber {
+    fn from(value: f64) -> Self {
+        if let Some(v) = serde_json::Number::from_f64(value) {
+            return SupervalueNumber::Finite(v);
+        } else if value.is_nan() {
+            return SupervalueNumber::Nan;
+        } else if value > 0. {
+            return SupervalueNumber::Infinity;
+        } else {
+            return SupervalueNumber::NegInfinity;
+        }
+    }
+}
+
+impl From<serde_yaml::Number> for SupervalueNumber {
+    fn from(value: serde_yaml::Number) -> Self {
+        if let Some(v) = value.as_u64() {
+            return v.into();
+        } else if let Some(v) = value.as_i64() {
+            return v.into();
+        } else {
+            return value.as_f64().unwrap().into();
+        }
+    }
+}
+
+impl SupervalueNumber {
+    /// True if the number was written without a fraction or exponent.
+    pub fn is_integer(&self) -> bool {
+        match self {
+            SupervalueNumber::Finite(v) => return !v.to_string().contains(['.', 'e', 'E']),
+            _ => return false,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            SupervalueNumber::Finite(v) => return v.as_u64(),
+            _ => return None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            SupervalueNumber::Finite(v) => return v.as_i64(),
+            _ => return None,
+        }
+    }
+
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            SupervalueNumber::Finite(v) if self.is_integer() => return v.to_string().parse::<i128>().ok(),
+            _ => return None,
+        }
+    }
+
+    /// The nearest `f64`, possibly losing precision (or infinite if out of
+    /// range).
+    pub fn as_f64_lossy(&self) -> f64 {
+        match self {
+            SupervalueNumber::Finite(v) => return v.to_string().parse::<f64>().unwrap(),
+            SupervalueNumber::Nan => return f64::NAN,
+            SupervalueNumber::Infinity => return f64::INFINITY,
+            SupervalueNumber::NegInfinity => return f64::NEG_INFINITY,
+        }
+    }
+
+    /// The `f64` with exactly the same value, if there is one.
+    pub fn as_f64_exact(&self) -> Option<f64> {
+        let f = self.as_f64_lossy();
+        match self {
+            SupervalueNumber::Finite(v) => {
+                let Some(back) = serde_json::Number::from_f64(f) else {
+                    return None;
+                };
+                if !decimal_eq(&back.to_string(), &v.to_string()) {
+                    return None;
+                }
+                return Some(f);
+            },
+            _ => return Some(f),
+        }
+    }
+
+    /// Convert to a primitive for a format named `target`. Integers are kept as
+    /// integers if they fit (`u64` only if `unsigned` is true), otherwise numbers
+    /// must be exactly representable as `f64` unless `lossy`.
+    pub fn to_primitive(&self, target: &str, unsigned: bool, lossy: bool) -> Result<NumberPrimitive, String> {
+        if self.is_integer() {
+            if let Some(v) = self.as_i64() {
+                return Ok(NumberPrimitive::I64(v));
+            }
+            if unsigned {
+                if let Some(v) = self.as_u64() {
+                    return Ok(NumberPrimitive::U64(v));
+                }
+            }
+            if !lossy {
+                return Err(
+                    format!(
+                        "Integer [{}] is out of range for {} (lossy number conversion is disabled)",
+                        self,
+                        target
+                    ),
+                );
+            }
+            return Ok(NumberPrimitive::F64(self.as_f64_lossy()));
+        }
+        if let Some(v) = self.as_f64_exact() {
+            return Ok(NumberPrimitive::F64(v));
+        }
+        if !lossy {
+            return Err(
+                format!(
+                    "Number [{}] can't be represented exactly in {} (lossy number conversion is disabled)",
+                    self,
+                    target
+                ),
+            );
+        }
+        return Ok(NumberPrimitive::F64(self.as_f64_lossy()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            NumberPrimitive,
+            SupervalueNumber,
+        },
+        std::str::FromStr,
+    };
+
+    fn n(text: &str) -> SupervalueNumber {
+        return SupervalueNumber::Finite(serde_json::Number::from_str(text).unwrap());
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(n("1"), n("1.0"));
+        assert_eq!(n("1000"), n("1e3"));
+        assert_eq!(n("0.10"), n("1E-1"));
+        assert_eq!(n("-0"), n("0.0"));
+        assert_ne!(n("1"), n("-1"));
+        assert_ne!(n("18446744073709551616"), n("18446744073709551617"));
+        assert_ne!(n("1e99999999999999999999"), n("1"));
+        assert_eq!(n("1e99999999999999999999"), n("10e99999999999999999998"));
+        assert_ne!(n("1e999999999999999999999999999999999999999"), n("1"));
+        assert_eq!(n("1e999999999999999999999999999999999999999"), n("1e999999999999999999999999999999999999999"));
+        assert_eq!(SupervalueNumber::from(f64::NAN), SupervalueNumber::Nan);
+        assert_ne!(SupervalueNumber::Infinity, SupervalueNumber::NegInfinity);
+    }
+
+    #[test]
+    fn primitive() {
+        assert_eq!(n("-5").to_primitive("x", true, false).unwrap(), NumberPrimitive::I64(-5));
+        assert_eq!(
+            n("18446744073709551615").to_primitive("x", true, false).unwrap(),
+            NumberPrimitive::U64(u64::MAX)
+        );
+        assert!(n("18446744073709551615").to_primitive("x", false, false).is_err());
+        assert_eq!(
+            n("18446744073709551615").to_primitive("x", false, true).unwrap(),
+            NumberPrimitive::F64(18446744073709551615.)
+        );
+        assert!(n("18446744073709551616").to_primitive("x", true, false).is_err());
+        assert_eq!(n("0.1").to_primitive("x", true, false).unwrap(), NumberPrimitive::F64(0.1));
+        assert_eq!(n("1.5e300").to_primitive("x", true, false).unwrap(), NumberPrimitive::F64(1.5e300));
+        assert!(n("0.10000000000000000001").to_primitive("x", true, false).is_err());
+        assert_eq!(n("0.10000000000000000001").to_primitive("x", true, true).unwrap(), NumberPrimitive::F64(0.1));
+        assert!(n("1e400").to_primitive("x", true, false).is_err());
+        assert!(matches!(SupervalueNumber::Nan.to_primitive("x", true, false), Ok(NumberPrimitive::F64(f)) if f.is_nan()));
+    }
+}
@@ -1,10 +1,24 @@
 use {
     aargvark::traits_impls::AargvarkFromStr,
+    std::{
+        fmt::Display,
+        str::FromStr,
+    },
 };
 
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct DataPath(pub Vec<serde_json::Value>);
 
-impl AargvarkFromStr for DataPath {
+impl DataPath {
+    /// The path of the first `len` segments.
+    pub fn prefix(&self, len: usize) -> DataPath {
+        return DataPath(self.0[..len].to_vec());
+    }
+}
+
+impl FromStr for DataPath {
+    type Err = String;
+
     fn from_str(s: &str) -> Result<Self, String> {
         if s.starts_with("[") {
             return Ok(
@@ -24,6 +38,19 @@ impl AargvarkFromStr for DataPath {
             return Err(format!("Paths must start with a [.]"));
         }
     }
+}
+
+impl Display for DataPath {
+    /// Formats as a JSON array, which is always a valid path argument.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", serde_json::to_string(&self.0).unwrap());
+    }
+}
+
+impl AargvarkFromStr for DataPath {
+    fn from_str(s: &str) -> Result<Self, String> {
+        return <Self as FromStr>::from_str(s);
+    }
 
     fn build_help_pattern(_state: &mut aargvark::help::HelpState) -> aargvark::help::HelpPattern {
         return aargvark::help::HelpPattern(vec![aargvark::help::HelpPatternElement::Type("PATH".to_string())]);
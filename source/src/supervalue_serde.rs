@@ -0,0 +1,910 @@
+use {
+    crate::{
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+            SupervalueVec,
+            SupervalueVecType,
+        },
+        supervalue_number::SupervalueNumber,
+    },
+    serde::{
+        de::{
+            self,
+            DeserializeOwned,
+            IntoDeserializer,
+            Visitor,
+        },
+        ser::{
+            self,
+            SerializeMap,
+            SerializeSeq,
+        },
+        Deserialize,
+        Serialize,
+    },
+    std::{
+        collections::HashMap,
+        fmt::Display,
+        str::FromStr,
+    },
+};
+
+const JSON_NUMBER_TOKEN: &str = "$serde_json::private::Number";
+const TOML_DATETIME_NAME: &str = "$__toml_private_Datetime";
+const TOML_DATETIME_FIELD: &str = "$__toml_private_datetime";
+const DATETIME_NAME: &str = "$hammer_of_json::private::Datetime";
+
+/// Errors from converting between `Supervalue` and other types via serde.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerdeError(pub String);
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(&self.0);
+    }
+}
+
+impl std::error::Error for SerdeError { }
+
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        return SerdeError(msg.to_string());
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        return SerdeError(msg.to_string());
+    }
+}
+
+/// Deserialize a Rust value from a `Supervalue`.
+pub fn from_supervalue<T: DeserializeOwned>(value: Supervalue) -> Result<T, SerdeError> {
+    return T::deserialize(value);
+}
+
+/// Serialize a Rust value into a `Supervalue`.
+pub fn to_supervalue<T: Serialize + ?Sized>(value: &T) -> Result<Supervalue, SerdeError> {
+    return value.serialize(SupervalueSerializer);
+}
+
+// # Serialize
+impl Serialize for Supervalue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Supervalue::Map(map) => {
+                if let Some((tag, value)) = map.as_tagged() {
+                    // Same as `serde_yaml::value::TaggedValue`, which the YAML serializer turns
+                    // back into a tag.
+                    struct SerializeTag(serde_yaml::value::Tag);
+
+                    impl Serialize for SerializeTag {
+                        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                            return serializer.collect_str(&self.0);
+                        }
+                    }
+
+                    let mut out = serializer.serialize_map(Some(1))?;
+                    out.serialize_entry(&SerializeTag(serde_yaml::value::Tag::new(tag)), value)?;
+                    return out.end();
+                }
+                if let SupervalueMapType::ComplexKeys(_) = &map.type_ {
+                    return <Supervalue as Into<serde_yaml::Value>>::into(self.clone()).serialize(serializer);
+                }
+                let mut keys = map.value.keys().collect::<Vec<_>>();
+                keys.sort();
+                let mut out = serializer.serialize_map(Some(keys.len()))?;
+                for k in keys {
+                    out.serialize_entry(k, &map.value[k])?;
+                }
+                return out.end();
+            },
+            Supervalue::Vec(vec) => {
+                if vec.type_ == SupervalueVecType::YamlMap {
+                    return <Supervalue as Into<serde_yaml::Value>>::into(self.clone()).serialize(serializer);
+                }
+                let mut out = serializer.serialize_seq(Some(vec.value.len()))?;
+                for e in &vec.value {
+                    out.serialize_element(e)?;
+                }
+                return out.end();
+            },
+            Supervalue::Null => return serializer.serialize_unit(),
+            Supervalue::Bool(v) => return serializer.serialize_bool(*v),
+            Supervalue::String(v) => return serializer.serialize_str(v),
+            Supervalue::Number(SupervalueNumber::Finite(v)) => {
+                if let Some(v) = v.as_u64() {
+                    return serializer.serialize_u64(v);
+                } else if let Some(v) = v.as_i64() {
+                    return serializer.serialize_i64(v);
+                } else if let Some(f) =
+                    v.as_f64().filter(|f| serde_json::Number::from_f64(*f).as_ref() == Some(v)) {
+                    return serializer.serialize_f64(f);
+                } else {
+                    // Not representable by primitives, use the `serde_json` arbitrary precision
+                    // representation
+                    return v.serialize(serializer);
+                }
+            },
+            Supervalue::Number(v) => return serializer.serialize_f64(v.as_f64_lossy()),
+            Supervalue::Datetime(v) => {
+                // A plain string for other serializers, recognized by `to_supervalue`
+                return serializer.serialize_newtype_struct(DATETIME_NAME, &v.to_string());
+            },
+            Supervalue::Bytes(v) => return serializer.serialize_bytes(v),
+        }
+    }
+}
+
+// # Deserialize
+struct SupervalueVisitor;
+
+impl<'de> Visitor<'de> for SupervalueVisitor {
+    type Value = Supervalue;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return formatter.write_str("any value");
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Bool(v));
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Number(v.into()));
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Number(v.into()));
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Number(serde_json::Number::from_str(&v.to_string()).map_err(E::custom)?.into()));
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Number(serde_json::Number::from_str(&v.to_string()).map_err(E::custom)?.into()));
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Number(v.into()));
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Supervalue, E> {
+        return Ok(Supervalue::String(v.to_string()));
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Supervalue, E> {
+        return Ok(Supervalue::String(v));
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Bytes(v.to_vec()));
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Bytes(v));
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Null);
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Supervalue, E> {
+        return Ok(Supervalue::Null);
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Supervalue, D::Error> {
+        return Supervalue::deserialize(deserializer);
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Supervalue, D::Error> {
+        return Supervalue::deserialize(deserializer);
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Supervalue, A::Error> {
+        let mut out = vec![];
+        while let Some(e) = seq.next_element::<Supervalue>()? {
+            out.push(e);
+        }
+        return Ok(Supervalue::Vec(SupervalueVec {
+            type_: SupervalueVecType::Normal,
+            value: out,
+        }));
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Supervalue, A::Error> {
+        let mut entries = vec![];
+        while let Some(k) = map.next_key::<Supervalue>()? {
+            // Magic representations of special values
+            if entries.is_empty() {
+                if let Supervalue::String(k) = &k {
+                    if k == JSON_NUMBER_TOKEN {
+                        let v = map.next_value::<String>()?;
+                        return Ok(
+                            Supervalue::Number(serde_json::Number::from_str(&v).map_err(de::Error::custom)?.into()),
+                        );
+                    } else if k == TOML_DATETIME_FIELD {
+                        let v = map.next_value::<String>()?;
+                        return Ok(
+                            Supervalue::Datetime(toml::value::Datetime::from_str(&v).map_err(de::Error::custom)?.into()),
+                        );
+                    }
+                }
+            }
+            let v = map.next_value::<Supervalue>()?;
+            entries.push((k, v));
+        }
+        return Ok(Supervalue::from_pairs(entries));
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Supervalue, A::Error> {
+        // Only YAML tags are represented as enums by the self-describing formats
+        use de::VariantAccess;
+
+        let (tag, contents) = data.variant::<String>()?;
+        let value = contents.newtype_variant::<Supervalue>()?;
+        return Ok(Supervalue::Map(SupervalueMap::new_tagged(serde_yaml::value::Tag::new(tag).to_string(), value)));
+    }
+}
+
+impl<'de> Deserialize<'de> for Supervalue {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return deserializer.deserialize_any(SupervalueVisitor);
+    }
+}
+
+// # Deserializer
+struct TagEnumAccess {
+    tag: String,
+    value: Supervalue,
+}
+
+impl<'de> de::EnumAccess<'de> for TagEnumAccess {
+    type Error = SerdeError;
+    type Variant = Supervalue;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Supervalue), SerdeError> {
+        let variant = seed.deserialize(<String as IntoDeserializer<SerdeError>>::into_deserializer(self.tag))?;
+        return Ok((variant, self.value));
+    }
+}
+
+struct MapEnumAccess {
+    variant: String,
+    value: Supervalue,
+}
+
+impl<'de> de::EnumAccess<'de> for MapEnumAccess {
+    type Error = SerdeError;
+    type Variant = Supervalue;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Supervalue), SerdeError> {
+        let variant = seed.deserialize(<String as IntoDeserializer<SerdeError>>::into_deserializer(self.variant))?;
+        return Ok((variant, self.value));
+    }
+}
+
+/// The variant contents for enums represented as `{"variant": contents}`
+impl<'de> de::VariantAccess<'de> for Supervalue {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        return <() as Deserialize>::deserialize(self);
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        return seed.deserialize(self);
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        return de::Deserializer::deserialize_seq(self, visitor);
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        return de::Deserializer::deserialize_map(self, visitor);
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Supervalue {
+    type Deserializer = Supervalue;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        return self;
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Supervalue {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Supervalue::Map(map) => {
+                if let Some((tag, value)) = map.as_tagged() {
+                    return visitor.visit_enum(TagEnumAccess {
+                        tag: tag.to_string(),
+                        value: value.clone(),
+                    });
+                }
+                if let SupervalueMapType::ComplexKeys(_) = &map.type_ {
+                    let entries =
+                        Supervalue::Map(map.clone())
+                            .map_pairs()
+                            .unwrap()
+                            .into_iter()
+                            .map(|(k, v)| (k, v.clone()))
+                            .collect::<Vec<_>>();
+                    let mut access = de::value::MapDeserializer::<_, SerdeError>::new(entries.into_iter());
+                    let out = visitor.visit_map(&mut access)?;
+                    access.end()?;
+                    return Ok(out);
+                }
+                let mut access = de::value::MapDeserializer::<_, SerdeError>::new(map.value.into_iter());
+                let out = visitor.visit_map(&mut access)?;
+                access.end()?;
+                return Ok(out);
+            },
+            Supervalue::Vec(vec) => {
+                let entries = match vec.type_ {
+                    SupervalueVecType::Normal => {
+                        let mut access = de::value::SeqDeserializer::<_, SerdeError>::new(vec.value.into_iter());
+                        let out = visitor.visit_seq(&mut access)?;
+                        access.end()?;
+                        return Ok(out);
+                    },
+                    SupervalueVecType::YamlMap => {
+                        let mut entries = vec![];
+                        for kv in vec.value {
+                            let Supervalue::Vec(kv) = kv else {
+                                return Err(de::Error::custom("Bad YAML complex map entry, not an array"));
+                            };
+                            let [k, v]: [Supervalue; 2] =
+                                kv
+                                    .value
+                                    .try_into()
+                                    .map_err(|_| de::Error::custom("Bad YAML complex map entry, not a pair"))?;
+                            entries.push((k, v));
+                        }
+                        entries
+                    },
+                };
+                let mut access = de::value::MapDeserializer::<_, SerdeError>::new(entries.into_iter());
+                let out = visitor.visit_map(&mut access)?;
+                access.end()?;
+                return Ok(out);
+            },
+            Supervalue::Null => return visitor.visit_unit(),
+            Supervalue::Bool(v) => return visitor.visit_bool(v),
+            Supervalue::String(v) => return visitor.visit_string(v),
+            Supervalue::Number(SupervalueNumber::Finite(v)) => return v
+                .deserialize_any(visitor)
+                .map_err(de::Error::custom),
+            Supervalue::Number(v) => return visitor.visit_f64(v.as_f64_lossy()),
+            Supervalue::Datetime(v) => return visitor.visit_string(v.to_string()),
+            Supervalue::Bytes(v) => return visitor.visit_byte_buf(v),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Supervalue::Null => return visitor.visit_none(),
+            v => return visitor.visit_some(v),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        return visitor.visit_newtype_struct(self);
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        if let Supervalue::Datetime(v) = &self {
+            if name == TOML_DATETIME_NAME {
+                let mut access =
+                    de::value::MapDeserializer::<_, SerdeError>::new(
+                        vec![(TOML_DATETIME_FIELD.to_string(), v.to_string())].into_iter(),
+                    );
+                let out = visitor.visit_map(&mut access)?;
+                access.end()?;
+                return Ok(out);
+            }
+        }
+        return self.deserialize_any(visitor);
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self {
+            Supervalue::String(v) => return visitor.visit_enum(v.into_deserializer()),
+            Supervalue::Map(map) => {
+                if let Some((tag, value)) = map.as_tagged() {
+                    return visitor.visit_enum(TagEnumAccess {
+                        tag: tag.trim_start_matches("!").to_string(),
+                        value: value.clone(),
+                    });
+                }
+                if map.value.len() != 1 {
+                    return Err(
+                        de::Error::custom(
+                            format!(
+                                "Expected enum represented as a map with a single key, but map has {} keys",
+                                map.value.len()
+                            ),
+                        ),
+                    );
+                }
+                let (variant, value) = map.value.into_iter().next().unwrap();
+                return visitor.visit_enum(MapEnumAccess {
+                    variant: variant,
+                    value: value,
+                });
+            },
+            other => return Err(
+                de::Error::custom(format!("Expected enum represented as a string or map, got {:?}", other)),
+            ),
+        }
+    }
+
+    serde::forward_to_deserialize_any!{
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map identifier ignored_any
+    }
+}
+
+// # Serializer
+/// Serializes Rust values into a `Supervalue`.
+pub struct SupervalueSerializer;
+
+fn variant_map(variant: &str, value: Supervalue) -> Supervalue {
+    let mut out = HashMap::new();
+    out.insert(variant.to_string(), value);
+    return Supervalue::Map(SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: out,
+    });
+}
+
+pub struct SerializeVec {
+    variant: Option<&'static str>,
+    value: Vec<Supervalue>,
+}
+
+impl SerializeVec {
+    fn finish(self) -> Supervalue {
+        let out = Supervalue::Vec(SupervalueVec {
+            type_: SupervalueVecType::Normal,
+            value: self.value,
+        });
+        match self.variant {
+            Some(variant) => return variant_map(variant, out),
+            None => return out,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.value.push(to_supervalue(value)?);
+        return Ok(());
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return Ok(self.finish());
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return ser::SerializeSeq::serialize_element(self, value);
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return Ok(self.finish());
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return ser::SerializeSeq::serialize_element(self, value);
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return Ok(self.finish());
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVec {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        return ser::SerializeSeq::serialize_element(self, value);
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return Ok(self.finish());
+    }
+}
+
+pub struct SerializeMapping {
+    variant: Option<&'static str>,
+    /// Set when serializing a struct with a magic name representing a special value
+    special: Option<&'static str>,
+    entries: Vec<(Supervalue, Supervalue)>,
+    next_key: Option<Supervalue>,
+}
+
+impl SerializeMapping {
+    fn finish(self) -> Result<Supervalue, SerdeError> {
+        if let Some(special) = self.special {
+            let Some((_, Supervalue::String(text))) = self.entries.into_iter().next() else {
+                return Err(SerdeError(format!("Special value {} missing string field", special)));
+            };
+            match special {
+                JSON_NUMBER_TOKEN => return Ok(
+                    Supervalue::Number(serde_json::Number::from_str(&text).map_err(ser::Error::custom)?.into()),
+                ),
+                TOML_DATETIME_NAME => return Ok(
+                    Supervalue::Datetime(toml::value::Datetime::from_str(&text).map_err(ser::Error::custom)?.into()),
+                ),
+                _ => unreachable!(),
+            }
+        }
+        let out = Supervalue::from_pairs(self.entries);
+        match self.variant {
+            Some(variant) => return Ok(variant_map(variant, out)),
+            None => return Ok(out),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeMapping {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.next_key = Some(to_supervalue(key)?);
+        return Ok(());
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let Some(key) = self.next_key.take() else {
+            return Err(SerdeError("Map value serialized before key".to_string()));
+        };
+        self.entries.push((key, to_supervalue(value)?));
+        return Ok(());
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return self.finish();
+    }
+}
+
+impl ser::SerializeStruct for SerializeMapping {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.entries.push((Supervalue::String(key.to_string()), to_supervalue(value)?));
+        return Ok(());
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return self.finish();
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMapping {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        return ser::SerializeStruct::serialize_field(self, key, value);
+    }
+
+    fn end(self) -> Result<Supervalue, SerdeError> {
+        return self.finish();
+    }
+}
+
+impl ser::Serializer for SupervalueSerializer {
+    type Ok = Supervalue;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVec;
+    type SerializeMap = SerializeMapping;
+    type SerializeStruct = SerializeMapping;
+    type SerializeStructVariant = SerializeMapping;
+
+    fn serialize_bool(self, v: bool) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Bool(v));
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Supervalue, SerdeError> {
+        return self.serialize_i64(v as i64);
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Supervalue, SerdeError> {
+        return self.serialize_i64(v as i64);
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Supervalue, SerdeError> {
+        return self.serialize_i64(v as i64);
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Number(v.into()));
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Supervalue, SerdeError> {
+        return Ok(
+            Supervalue::Number(serde_json::Number::from_str(&v.to_string()).map_err(ser::Error::custom)?.into()),
+        );
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Supervalue, SerdeError> {
+        return self.serialize_u64(v as u64);
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Supervalue, SerdeError> {
+        return self.serialize_u64(v as u64);
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Supervalue, SerdeError> {
+        return self.serialize_u64(v as u64);
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Number(v.into()));
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Supervalue, SerdeError> {
+        return Ok(
+            Supervalue::Number(serde_json::Number::from_str(&v.to_string()).map_err(ser::Error::custom)?.into()),
+        );
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Supervalue, SerdeError> {
+        return self.serialize_f64(v as f64);
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Supervalue, SerdeError> {
+        return SupervalueVisitor.visit_f64(v);
+    }
+
+    fn serialize_char(self, v: char) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::String(v.to_string()));
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::String(v.to_string()));
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Supervalue, SerdeError> {
+        return SupervalueVisitor.visit_bytes(v);
+    }
+
+    fn serialize_none(self) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Null);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Supervalue, SerdeError> {
+        return to_supervalue(value);
+    }
+
+    fn serialize_unit(self) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Null);
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::Null);
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Supervalue, SerdeError> {
+        return Ok(Supervalue::String(variant.to_string()));
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Supervalue, SerdeError> {
+        let out = to_supervalue(value)?;
+        if name == DATETIME_NAME {
+            let Supervalue::String(text) = out else {
+                return Err(SerdeError(format!("Special value {} isn't a string", name)));
+            };
+            return Ok(
+                Supervalue::Datetime(toml::value::Datetime::from_str(&text).map_err(ser::Error::custom)?.into()),
+            );
+        }
+        return Ok(out);
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Supervalue, SerdeError> {
+        return Ok(variant_map(variant, to_supervalue(value)?));
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, SerdeError> {
+        return Ok(SerializeVec {
+            variant: None,
+            value: Vec::with_capacity(len.unwrap_or(0)),
+        });
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, SerdeError> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeVec, SerdeError> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, SerdeError> {
+        return Ok(SerializeVec {
+            variant: Some(variant),
+            value: Vec::with_capacity(len),
+        });
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMapping, SerdeError> {
+        return Ok(SerializeMapping {
+            variant: None,
+            special: None,
+            entries: vec![],
+            next_key: None,
+        });
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<SerializeMapping, SerdeError> {
+        return Ok(SerializeMapping {
+            variant: None,
+            special: match name {
+                JSON_NUMBER_TOKEN => Some(JSON_NUMBER_TOKEN),
+                TOML_DATETIME_NAME => Some(TOML_DATETIME_NAME),
+                _ => None,
+            },
+            entries: vec![],
+            next_key: None,
+        });
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeMapping, SerdeError> {
+        return Ok(SerializeMapping {
+            variant: Some(variant),
+            special: None,
+            entries: vec![],
+            next_key: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_supervalue,
+            to_supervalue,
+        },
+        crate::supervalue::Supervalue,
+        serde::{
+            Deserialize,
+            Serialize,
+        },
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Limited(u32),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        #[serde(rename = "listenAddr")]
+        listen_addr: String,
+        #[serde(default)]
+        retries: Option<u64>,
+        modes: Vec<Mode>,
+        created: toml::value::Datetime,
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = Supervalue::from(toml::from_str::<toml::Value>(r#"
+listenAddr = "[::]:80"
+modes = ["Fast", { Limited = 4 }]
+created = 2024-01-02T03:04:05Z
+"#).unwrap());
+        let config = from_supervalue::<Config>(source.clone()).unwrap();
+        assert_eq!(config, Config {
+            listen_addr: "[::]:80".to_string(),
+            retries: None,
+            modes: vec![Mode::Fast, Mode::Limited(4)],
+            created: "2024-01-02T03:04:05Z".parse().unwrap(),
+        });
+        let mut expected = source;
+        expected.set_path(&".retries".parse().unwrap(), Supervalue::Null).unwrap();
+        assert_eq!(to_supervalue(&config).unwrap(), expected);
+    }
+
+    #[test]
+    fn datetime() {
+        let source = Supervalue::from(toml::from_str::<toml::Value>("a = 2024-01-02T03:04:05Z").unwrap());
+        assert_eq!(serde_json::to_value(&source).unwrap(), serde_json::json!({
+            "a": "2024-01-02T03:04:05Z"
+        }));
+        assert_eq!(serde_yaml::to_string(&source).unwrap(), "a: 2024-01-02T03:04:05Z\n");
+        assert_eq!(to_supervalue(&source).unwrap(), source);
+    }
+
+    #[test]
+    fn direct_deserialize() {
+        let text = "a: !Ref foo\nb: [x, null, true]\n";
+        assert_eq!(
+            serde_yaml::from_str::<Supervalue>(text).unwrap(),
+            Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>(text).unwrap())
+        );
+        let text = r#"{"a": 123456789012345678901234567890, "b": [true, "x"]}"#;
+        assert_eq!(
+            serde_json::from_str::<Supervalue>(text).unwrap(),
+            Supervalue::from(serde_json::from_str::<serde_json::Value>(text).unwrap())
+        );
+        assert_eq!(
+            serde_json::to_string(&serde_json::from_str::<Supervalue>(text).unwrap()).unwrap(),
+            r#"{"a":123456789012345678901234567890,"b":[true,"x"]}"#
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use {
+    crate::{
+        error::Error,
+        supervalue::{
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+            SupervalueVec,
+            SupervalueVecType,
+        },
+    },
+    base64::prelude::{
+        BASE64_STANDARD,
+        Engine,
+    },
+    std::collections::HashMap,
+};
+
+/// Parse delimited text with a header row into an array of maps from header
+/// names to (string) cell values.
+pub fn from_csv_str(text: &str, delimiter: u8) -> Result<Supervalue, String> {
+    let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(|e| format!("Error reading header row: {}", e))?.clone();
+    let mut rows = vec![];
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Error reading row {}: {}", i + 1, e))?;
+        rows.push(Supervalue::Map(SupervalueMap {
+            type_: SupervalueMapType::Normal,
+            value: headers
+                .iter()
+                .zip(record.iter())
+                .map(|(k, v)| (k.to_string(), Supervalue::String(v.to_string())))
+                .collect(),
+        }));
+    }
+    return Ok(Supervalue::Vec(SupervalueVec {
+        type_: SupervalueVecType::Normal,
+        value: rows,
+    }));
+}
+
+/// Parse a column list (like `--csv-columns`) as a single delimited record, so
+/// names containing the delimiter can be quoted (`"a,b",c`).
+pub fn parse_csv_columns(text: &str, delimiter: u8) -> Result<Vec<String>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut records = reader.records();
+    let Some(record) = records.next() else {
+        return Ok(vec![]);
+    };
+    let record = record.map_err(|e| format!("Error reading column list: {}", e))?;
+    if records.next().is_some() {
+        return Err(format!("Column list must be a single line"));
+    }
+    return Ok(record.iter().map(|c| c.to_string()).collect());
+}
+
+fn flatten_row(
+    out: &mut HashMap<String, String>,
+    columns: &mut Vec<String>,
+    prefix: &str,
+    value: &Supervalue,
+) -> Result<(), Error> {
+    match value {
+        Supervalue::Map(map) => {
+            let mut keys = map.value.keys().collect::<Vec<_>>();
+            keys.sort();
+            for k in keys {
+                let path = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                flatten_row(out, columns, &path, &map.value[k])?;
+            }
+        },
+        v => {
+            let cell = match v {
+                Supervalue::Null => String::new(),
+                Supervalue::Bool(v) => v.to_string(),
+                Supervalue::String(v) => v.clone(),
+                Supervalue::Number(v) => v.to_string(),
+                Supervalue::Datetime(v) => v.to_string(),
+                Supervalue::Bytes(v) => BASE64_STANDARD.encode(v),
+                v => serde_json::to_string(&<Supervalue as Into<serde_json::Value>>::into(v.clone())).unwrap(),
+            };
+            if out.contains_key(prefix) {
+                return Err(Error::FlattenConflict { key: prefix.to_string() });
+            }
+            if !columns.iter().any(|c| c == prefix) {
+                columns.push(prefix.to_string());
+            }
+            out.insert(prefix.to_string(), cell);
+        },
+    }
+    return Ok(());
+}
+
+/// Write an array of maps as delimited text with a header row. Nested maps are
+/// flattened with dotted column names (`a.b`), and arrays are written as JSON.
+/// If `columns` is specified only those columns are written, in that order,
+/// otherwise all columns are written in order of first appearance. Flattened
+/// names that collide (a key `a.b` and a nested `a` → `b`) are an error.
+pub fn to_csv_string(value: &Supervalue, delimiter: u8, columns: Option<&[String]>) -> Result<String, String> {
+    let Supervalue::Vec(rows) = value else {
+        return Err(format!("Delimited output requires an array of objects, but the data isn't an array"));
+    };
+    let mut found_columns = vec![];
+    let mut flat_rows = vec![];
+    for (i, row) in rows.value.iter().enumerate() {
+        let Supervalue::Map(_) = row else {
+            return Err(format!("Delimited output requires an array of objects, but row {} isn't an object", i));
+        };
+        let mut flat_row = HashMap::new();
+        flatten_row(&mut flat_row, &mut found_columns, "", row).map_err(|e| e.to_string())?;
+        flat_rows.push(flat_row);
+    }
+    let columns = columns.map(|c| c.to_vec()).unwrap_or(found_columns);
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(vec![]);
+    writer.write_record(&columns).map_err(|e| e.to_string())?;
+    for row in flat_rows {
+        writer
+            .write_record(columns.iter().map(|c| row.get(c).map(|v| v.as_str()).unwrap_or("")))
+            .map_err(|e| e.to_string())?;
+    }
+    return Ok(String::from_utf8(writer.into_inner().map_err(|e| e.to_string())?).unwrap());
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_csv_str,
+            parse_csv_columns,
+            to_csv_string,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn write() {
+        let source = Supervalue::from(json!([{
+            "name": "a, b",
+            "stats": {
+                "n": 1,
+                "tags": ["x"]
+            },
+        }, {
+            "name": "c",
+            "extra": true,
+        }]));
+        assert_eq!(
+            to_csv_string(&source, b',', None).unwrap(),
+            "name,stats.n,stats.tags,extra\n\"a, b\",1,\"[\"\"x\"\"]\",\nc,,,true\n"
+        );
+        assert_eq!(
+            to_csv_string(&source, b'\t', Some(&["extra".to_string(), "name".to_string()])).unwrap(),
+            "extra\tname\n\ta, b\ntrue\tc\n"
+        );
+    }
+
+    #[test]
+    fn write_conflict() {
+        let source = Supervalue::from(json!([{
+            "a.b": 1,
+            "a": {
+                "b": 2
+            },
+        }]));
+        assert_eq!(
+            to_csv_string(&source, b',', None).unwrap_err(),
+            "Flattened key [\"a.b\"] conflicts with another key"
+        );
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(parse_csv_columns("name, stats.n", b',').unwrap(), vec!["name".to_string(), "stats.n".to_string()]);
+        assert_eq!(parse_csv_columns("\"a, b\",c", b',').unwrap(), vec!["a, b".to_string(), "c".to_string()]);
+        assert!(parse_csv_columns("a\nb", b',').is_err());
+    }
+
+    #[test]
+    fn read() {
+        assert_eq!(from_csv_str("a,b\n1,\"x,y\"\n", b',').unwrap(), Supervalue::from(json!([{
+            "a": "1",
+            "b": "x,y"
+        }])));
+    }
+}
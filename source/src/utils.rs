@@ -1,5 +1,6 @@
 use {
     crate::{
+        error::Error,
         supervalue::{
             Supervalue,
             SupervalueMap,
@@ -27,6 +28,37 @@ pub enum AtPathResVec<T> {
     Err,
 }
 
+/// Interpret a path segment as a map key.
+pub fn map_key_segment<'a>(path: &DataPath, depth: usize, seg: &'a serde_json::Value) -> Result<&'a str, Error> {
+    match seg {
+        serde_json::Value::String(s) => return Ok(s),
+        _ => return Err(Error::TypeMismatch {
+            path: path.prefix(depth),
+            segment: seg.clone(),
+        }),
+    }
+}
+
+/// Interpret a path segment as an array index. Strings containing numbers are
+/// accepted so that `.`-style paths can index arrays.
+pub fn vec_index_segment(path: &DataPath, depth: usize, seg: &serde_json::Value) -> Result<usize, Error> {
+    let mismatch = || Error::TypeMismatch {
+        path: path.prefix(depth),
+        segment: seg.clone(),
+    };
+    match seg {
+        serde_json::Value::String(s) => return str::parse::<usize>(s).map_err(|_| mismatch()),
+        serde_json::Value::Number(n) => {
+            let n = n.as_f64().unwrap();
+            if n < 0. {
+                return Err(mismatch());
+            }
+            return Ok(n as usize);
+        },
+        _ => return Err(mismatch()),
+    }
+}
+
 pub fn at_path<
     T,
 >(
@@ -36,11 +68,11 @@ pub fn at_path<
     mut handle_early_missing_vec: impl FnMut() -> AtPathResVec<T>,
     mut handle_early_untraversible: impl FnMut() -> AtPathEarlyRes<T>,
     handle_end_missing: impl FnOnce(&mut SupervalueMap, &str) -> AtPathEndRes<T>,
-    handle_end_found: impl FnOnce(&mut SupervalueMap, &str) -> Result<T, String>,
+    handle_end_found: impl FnOnce(&mut SupervalueMap, &str) -> Result<T, Error>,
     handle_end_missing_vec: impl FnOnce(&mut SupervalueVec, usize) -> AtPathResVec<T>,
-    handle_end_found_vec: impl FnOnce(&mut SupervalueVec, usize) -> Result<T, String>,
-    handle_end_root: impl FnOnce(&mut Supervalue) -> Result<T, String>,
-) -> Result<T, String> {
+    handle_end_found_vec: impl FnOnce(&mut SupervalueVec, usize) -> Result<T, Error>,
+    handle_end_root: impl FnOnce(&mut Supervalue) -> Result<T, Error>,
+) -> Result<T, Error> {
     if path.0.is_empty() {
         return handle_end_root(at);
     } else {
@@ -62,30 +94,14 @@ pub fn at_path<
                             *at = Supervalue::Map(Default::default());
                         },
                         AtPathEarlyRes::Err => {
-                            return Err(
-                                format!(
-                                    "Encountered primitive value at {:?}, before reaching end of path",
-                                    &path.0[0 ..= depth]
-                                ),
-                            );
+                            return Err(Error::Untraversable { path: path.prefix(depth) });
                         },
                     }
                 },
             }
             match at {
                 Supervalue::Map(map) => {
-                    let seg = match seg {
-                        serde_json::Value::String(s) => s,
-                        _ => {
-                            return Err(
-                                format!(
-                                    "At a map, but path contains non-string segment {} [{}]",
-                                    depth,
-                                    serde_json::to_string(seg).unwrap()
-                                ),
-                            );
-                        },
-                    };
+                    let seg = map_key_segment(path, depth, seg)?;
                     if last {
                         if map.value.contains_key(seg) {
                             return handle_end_found(map, seg);
@@ -99,18 +115,15 @@ pub fn at_path<
                                     return Ok(ret);
                                 },
                                 AtPathEndRes::Err => {
-                                    return Err(
-                                        format!(
-                                            "Encountered object value at {:?} but the key [{:?}] is missing",
-                                            &path.0[0 .. depth],
-                                            seg
-                                        ),
-                                    );
+                                    return Err(Error::MissingKey {
+                                        path: path.prefix(depth),
+                                        key: seg.to_string(),
+                                    });
                                 },
                             }
                         }
                     } else {
-                        let v = match map.value.entry(seg.clone()) {
+                        let v = match map.value.entry(seg.to_string()) {
                             Entry::Occupied(v) => v.into_mut(),
                             Entry::Vacant(en) => match handle_early_missing() {
                                 AtPathEarlyRes::Return(v) => {
@@ -120,13 +133,10 @@ pub fn at_path<
                                     en.insert(Supervalue::Map(Default::default()))
                                 },
                                 AtPathEarlyRes::Err => {
-                                    return Err(
-                                        format!(
-                                            "Encountered object value at {:?} but the key [{:?}] is missing",
-                                            &path.0[0 .. depth],
-                                            seg
-                                        ),
-                                    );
+                                    return Err(Error::MissingKey {
+                                        path: path.prefix(depth),
+                                        key: seg.to_string(),
+                                    });
                                 },
                             },
                         };
@@ -134,39 +144,7 @@ pub fn at_path<
                     }
                 },
                 Supervalue::Vec(ve) => {
-                    let seg = match seg {
-                        serde_json::Value::String(s) => match str::parse::<usize>(s) {
-                            Ok(seg) => seg,
-                            Err(e) => {
-                                return Err(
-                                    format!(
-                                        "At an array, but path contains non-numberlike segment {} [{}]: {}",
-                                        depth,
-                                        serde_json::to_string(seg).unwrap(),
-                                        e
-                                    ),
-                                );
-                            },
-                        },
-                        serde_json::Value::Number(n) => {
-                            let n = n.as_f64().unwrap();
-                            if n < 0. {
-                                return Err(
-                                    format!("At an array, but path contains negative index {} [{}]", depth, n),
-                                );
-                            }
-                            n as usize
-                        },
-                        _ => {
-                            return Err(
-                                format!(
-                                    "At a map, but path contains non-string segment {} [{}]",
-                                    depth,
-                                    serde_json::to_string(seg).unwrap()
-                                ),
-                            );
-                        },
-                    };
+                    let seg = vec_index_segment(path, depth, seg)?;
                     if last {
                         if seg < ve.value.len() {
                             return handle_end_found_vec(ve, seg);
@@ -176,13 +154,10 @@ pub fn at_path<
                                     return Ok(v);
                                 },
                                 AtPathResVec::Err => {
-                                    return Err(
-                                        format!(
-                                            "Encountered array value at {:?} but the key [{:?}] is out of bounds",
-                                            &path.0[0 .. depth],
-                                            seg
-                                        ),
-                                    );
+                                    return Err(Error::OutOfBounds {
+                                        path: path.prefix(depth),
+                                        index: seg,
+                                    });
                                 },
                             }
                         }
@@ -193,13 +168,10 @@ pub fn at_path<
                                     return Ok(v);
                                 },
                                 AtPathResVec::Err => {
-                                    return Err(
-                                        format!(
-                                            "Encountered object value at {:?} but the key [{:?}] is missing",
-                                            &path.0[0 .. depth],
-                                            seg
-                                        ),
-                                    );
+                                    return Err(Error::OutOfBounds {
+                                        path: path.prefix(depth),
+                                        index: seg,
+                                    });
                                 },
                             }
                         };
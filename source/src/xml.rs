@@ -0,0 +1,413 @@
+use {
+    crate::supervalue::{
+        Supervalue,
+        SupervalueMap,
+        SupervalueMapType,
+        SupervalueVec,
+        SupervalueVecType,
+    },
+    base64::prelude::{
+        BASE64_STANDARD,
+        Engine,
+    },
+    quick_xml::{
+        escape::resolve_predefined_entity,
+        events::{
+            BytesDecl,
+            BytesEnd,
+            BytesStart,
+            BytesText,
+            Event,
+        },
+        Reader,
+        Writer,
+    },
+    std::collections::HashMap,
+};
+
+pub const XML_ATTR_PREFIX: &str = "@";
+pub const XML_TEXT_KEY: &str = "#text";
+
+struct PartialElement {
+    name: String,
+    value: HashMap<String, Supervalue>,
+    text: String,
+}
+
+impl PartialElement {
+    fn new(e: &BytesStart) -> Result<Self, String> {
+        let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+        let mut value = HashMap::new();
+        for attr in e.attributes() {
+            let attr = attr.map_err(|e| format!("Invalid attribute in element [{}]: {}", name, e))?;
+            let attr_value =
+                attr
+                    .unescape_value()
+                    .map_err(|e| format!("Invalid attribute value in element [{}]: {}", name, e))?
+                    .to_string();
+            value.insert(
+                format!("{}{}", XML_ATTR_PREFIX, String::from_utf8_lossy(attr.key.as_ref())),
+                Supervalue::String(attr_value),
+            );
+        }
+        return Ok(PartialElement {
+            name: name,
+            value: value,
+            text: String::new(),
+        });
+    }
+
+    fn add_child(&mut self, name: String, child: Supervalue) {
+        match self.value.get_mut(&name) {
+            Some(Supervalue::Vec(existing)) => {
+                existing.value.push(child);
+            },
+            Some(existing) => {
+                let first = std::mem::replace(existing, Supervalue::Null);
+                *existing = Supervalue::Vec(SupervalueVec {
+                    type_: SupervalueVecType::Normal,
+                    value: vec![first, child],
+                });
+            },
+            None => {
+                self.value.insert(name, child);
+            },
+        }
+    }
+
+    fn finish(self) -> (String, Supervalue) {
+        let text = self.text.trim();
+        if self.value.is_empty() {
+            if text.is_empty() {
+                return (self.name, Supervalue::Null);
+            }
+            return (self.name, Supervalue::String(text.to_string()));
+        }
+        let mut value = self.value;
+        if !text.is_empty() {
+            value.insert(XML_TEXT_KEY.to_string(), Supervalue::String(text.to_string()));
+        }
+        return (self.name, Supervalue::Map(SupervalueMap {
+            type_: SupervalueMapType::Normal,
+            value: value,
+        }));
+    }
+}
+
+/// Parse an XML document into data:
+///
+/// * The document is a map with a single key, the root element name
+///
+/// * An element with attributes or child elements is a map, with attributes as
+///   `@name` keys, child elements under their (qualified) names, and any text as
+///   `#text`
+///
+/// * An element with only text is a string, and an empty element is `null`
+///
+/// * Repeated child elements are an array
+///
+/// All attribute values and text are strings. Comments, processing instructions,
+/// and the relative order of differently named children are lost. Text is
+/// trimmed, and in mixed content all text pieces are joined into one `#text`
+/// regardless of where they appeared between children.
+pub fn from_xml_str(text: &str) -> Result<Supervalue, String> {
+    let mut reader = Reader::from_str(text);
+    let mut stack: Vec<PartialElement> = vec![];
+    let mut root = None;
+    let mut finish = |stack: &mut Vec<PartialElement>, element: PartialElement| -> Result<(), String> {
+        let (name, value) = element.finish();
+        match stack.last_mut() {
+            Some(parent) => parent.add_child(name, value),
+            None => {
+                if root.is_some() {
+                    return Err(format!("Document has multiple root elements, found second root [{}]", name));
+                }
+                root = Some((name, value));
+            },
+        }
+        return Ok(());
+    };
+    loop {
+        let event =
+            reader
+                .read_event()
+                .map_err(|e| format!("Error parsing XML at position {}: {}", reader.error_position(), e))?;
+        match event {
+            Event::Start(e) => {
+                stack.push(PartialElement::new(&e)?);
+            },
+            Event::Empty(e) => {
+                let element = PartialElement::new(&e)?;
+                finish(&mut stack, element)?;
+            },
+            Event::End(_) => {
+                let element = stack.pop().ok_or_else(|| format!("Unmatched closing tag"))?;
+                finish(&mut stack, element)?;
+            },
+            Event::Text(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.decode().map_err(|e| format!("Invalid text in XML: {}", e))?);
+                }
+            },
+            Event::CData(e) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&e.decode().map_err(|e| format!("Invalid CDATA in XML: {}", e))?);
+                }
+            },
+            Event::GeneralRef(e) => {
+                let Some(element) = stack.last_mut() else {
+                    continue;
+                };
+                if let Some(c) = e.resolve_char_ref().map_err(|e| format!("Invalid character reference: {}", e))? {
+                    element.text.push(c);
+                } else {
+                    let name = e.decode().map_err(|e| format!("Invalid entity reference: {}", e))?;
+                    let Some(resolved) = resolve_predefined_entity(&name) else {
+                        return Err(format!("Unknown entity reference [&{};]", name));
+                    };
+                    element.text.push_str(resolved);
+                }
+            },
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => { },
+            Event::Eof => break,
+        }
+    }
+    if let Some(element) = stack.last() {
+        return Err(format!("Element [{}] isn't closed", element.name));
+    }
+    let Some((name, value)) = root else {
+        return Err(format!("Document has no root element"));
+    };
+    return Ok(Supervalue::Map(SupervalueMap {
+        type_: SupervalueMapType::Normal,
+        value: [(name, value)].into_iter().collect(),
+    }));
+}
+
+fn scalar_text(path: &str, value: &Supervalue) -> Result<String, String> {
+    match value {
+        Supervalue::Null => return Ok(String::new()),
+        Supervalue::Bool(v) => return Ok(v.to_string()),
+        Supervalue::String(v) => return Ok(v.clone()),
+        Supervalue::Number(v) => return Ok(v.to_string()),
+        Supervalue::Datetime(v) => return Ok(v.to_string()),
+        Supervalue::Bytes(v) => return Ok(BASE64_STANDARD.encode(v)),
+        Supervalue::Map(_) | Supervalue::Vec(_) => {
+            return Err(format!("Value at [{}] must be a primitive to be written as XML text or attribute", path));
+        },
+    }
+}
+
+/// Whether `name` is a valid XML name (element or attribute name, optionally
+/// qualified like `android:name`).
+fn is_xml_name(name: &str) -> bool {
+    fn is_start(c: char) -> bool {
+        return matches!(
+            c,
+            ':' | 'A' ..= 'Z' | '_' | 'a' ..= 'z' | '\u{C0}' ..= '\u{D6}' | '\u{D8}' ..= '\u{F6}' | '\u{F8}' ..= '\u{2FF}' |
+            '\u{370}' ..= '\u{37D}' | '\u{37F}' ..= '\u{1FFF}' | '\u{200C}' ..= '\u{200D}' | '\u{2070}' ..= '\u{218F}' |
+            '\u{2C00}' ..= '\u{2FEF}' | '\u{3001}' ..= '\u{D7FF}' | '\u{F900}' ..= '\u{FDCF}' | '\u{FDF0}' ..= '\u{FFFD}' |
+            '\u{10000}' ..= '\u{EFFFF}'
+        );
+    }
+
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    return is_start(first) &&
+        chars.all(
+            |c| is_start(c) ||
+                matches!(c, '-' | '.' | '0' ..= '9' | '\u{B7}' | '\u{300}' ..= '\u{36F}' | '\u{203F}' ..= '\u{2040}'),
+        );
+}
+
+fn write_element(
+    writer: &mut Writer<Vec<u8>>,
+    path: &str,
+    name: &str,
+    value: &Supervalue,
+    in_array: bool,
+) -> Result<(), String> {
+    let parent_path = path;
+    let path = format!("{}/{}", parent_path, name);
+    if !is_xml_name(name) {
+        return Err(format!("Key at [{}] isn't a valid XML element name", path));
+    }
+    match value {
+        Supervalue::Vec(v) => {
+            if in_array || v.type_ != SupervalueVecType::Normal {
+                return Err(format!("Array at [{}] can't be written as XML, only arrays of elements are allowed", path));
+            }
+            for e in &v.value {
+                write_element(writer, parent_path, name, e, true)?;
+            }
+        },
+        Supervalue::Map(v) => {
+            let mut keys = v.value.keys().collect::<Vec<_>>();
+            keys.sort();
+            let mut start = BytesStart::new(name);
+            let mut text = None;
+            let mut children = vec![];
+            for k in keys {
+                let child = &v.value[k];
+                if let Some(attr) = k.strip_prefix(XML_ATTR_PREFIX) {
+                    if !is_xml_name(attr) {
+                        return Err(format!("Key at [{}/{}] isn't a valid XML attribute name", path, k));
+                    }
+                    start.push_attribute((attr, scalar_text(&format!("{}/{}", path, k), child)?.as_str()));
+                } else if k == XML_TEXT_KEY {
+                    text = Some(scalar_text(&format!("{}/{}", path, k), child)?);
+                } else {
+                    children.push((k, child));
+                }
+            }
+            if text.is_none() && children.is_empty() {
+                writer.write_event(Event::Empty(start)).unwrap();
+                return Ok(());
+            }
+            writer.write_event(Event::Start(start)).unwrap();
+            if let Some(text) = text {
+                writer.write_event(Event::Text(BytesText::new(&text))).unwrap();
+            }
+            for (k, child) in children {
+                write_element(writer, &path, k, child, false)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new(name))).unwrap();
+        },
+        Supervalue::Null => {
+            writer.write_event(Event::Empty(BytesStart::new(name))).unwrap();
+        },
+        v => {
+            let text = scalar_text(&path, v)?;
+            writer.write_event(Event::Start(BytesStart::new(name))).unwrap();
+            writer.write_event(Event::Text(BytesText::new(&text))).unwrap();
+            writer.write_event(Event::End(BytesEnd::new(name))).unwrap();
+        },
+    }
+    return Ok(());
+}
+
+/// Write data as an indented XML document, the reverse of `from_xml_str`. The
+/// data must be a map with a single key, the root element.
+pub fn to_xml_string(value: &Supervalue) -> Result<String, String> {
+    let root = root_element(value)?;
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None))).unwrap();
+    write_element(&mut writer, "", root.0, root.1, false)?;
+    let mut out = String::from_utf8(writer.into_inner()).unwrap();
+    out.push('\n');
+    return Ok(out);
+}
+
+fn root_element(value: &Supervalue) -> Result<(&String, &Supervalue), String> {
+    let Supervalue::Map(map) = value else {
+        return Err(format!("XML output requires a map with a single key (the root element), but the data isn't a map"));
+    };
+    let mut entries = map.value.iter();
+    let (Some(root), None) = (entries.next(), entries.next()) else {
+        return Err(
+            format!(
+                "XML output requires a map with a single key (the root element), but the map has {} keys",
+                map.value.len()
+            ),
+        );
+    };
+    if root.0.starts_with(XML_ATTR_PREFIX) || root.0 == XML_TEXT_KEY {
+        return Err(format!("The root element name [{}] isn't a valid element name", root.0));
+    }
+    if let Supervalue::Vec(_) = root.1 {
+        return Err(format!("The root element [{}] is an array, but XML documents have a single root", root.0));
+    }
+    return Ok(root);
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            from_xml_str,
+            to_xml_string,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn mapping() {
+        let source =
+            from_xml_str(
+                r#"<?xml version="1.0"?>
+<!-- comment -->
+<project xmlns:a="x" a:v="1">
+  <name>A &amp; B&#33;</name>
+  <dep id="1"><![CDATA[x<y]]></dep>
+  <dep id="2"/>
+  <empty></empty>
+</project>"#,
+            ).unwrap();
+        assert_eq!(source, Supervalue::from(json!({
+            "project": {
+                "@xmlns:a": "x",
+                "@a:v": "1",
+                "name": "A & B!",
+                "dep": [{
+                    "@id": "1",
+                    "#text": "x<y",
+                }, {
+                    "@id": "2",
+                }],
+                "empty": null,
+            }
+        })));
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = Supervalue::from(json!({
+            "project": {
+                "@v": "1",
+                "dep": [{
+                    "@id": "1",
+                    "#text": "a<b",
+                }, "plain"],
+                "empty": null,
+            }
+        }));
+        let text = to_xml_string(&source).unwrap();
+        assert_eq!(
+            text,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<project v=\"1\">\n  <dep id=\"1\">a&lt;b</dep>\n  <dep>plain</dep>\n  <empty/>\n</project>\n"
+        );
+        assert_eq!(from_xml_str(&text).unwrap(), source);
+        assert!(to_xml_string(&Supervalue::from(json!({
+            "a": 1,
+            "b": 2
+        }))).is_err());
+    }
+
+    #[test]
+    fn invalid_names() {
+        for source in [json!({
+            "a b": 1
+        }), json!({
+            "a": {
+                "1x": 1
+            }
+        }), json!({
+            "a": {
+                "@x=": "1"
+            }
+        }), json!({
+            "": 1
+        })] {
+            assert!(to_xml_string(&Supervalue::from(source)).is_err());
+        }
+        assert!(to_xml_string(&Supervalue::from(json!({
+            "a:b": {
+                "@xmlns:a": "x",
+                "c-d.e_2": "é"
+            }
+        }))).is_ok());
+    }
+}
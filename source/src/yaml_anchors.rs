@@ -0,0 +1,515 @@
+use {
+    crate::{
+        supervalue::{
+            ConvertOptions,
+            Supervalue,
+            SupervalueMap,
+            SupervalueMapType,
+        },
+        supervalue_path::DataPath,
+    },
+    std::{
+        collections::{
+            HashMap,
+            HashSet,
+        },
+        ffi::CStr,
+        marker::PhantomData,
+        mem::MaybeUninit,
+    },
+    unsafe_libyaml as sys,
+};
+
+/// The YAML 1.1 merge key.
+pub const YAML_MERGE_KEY: &str = "<<";
+
+/// Recursively resolve YAML 1.1 merge keys: the entries of the map (or each map
+/// in the array of maps) under a `<<` key are added to the map containing it.
+/// Keys already in the map take precedence, then earlier maps in the array.
+pub fn resolve_merge_keys(source: Supervalue) -> Result<Supervalue, String> {
+    match source {
+        Supervalue::Map(map) => {
+            let mut type_ = map.type_;
+            let mut value = HashMap::new();
+            for (k, v) in map.value {
+                value.insert(k, resolve_merge_keys(v)?);
+            }
+            let Some(merge) = value.remove(YAML_MERGE_KEY) else {
+                return Ok(Supervalue::Map(SupervalueMap {
+                    type_: type_,
+                    value: value,
+                }));
+            };
+            let merge = match merge {
+                Supervalue::Map(m) => vec![m],
+                Supervalue::Vec(v) => v.value.into_iter().map(|m| match m {
+                    Supervalue::Map(m) => return Ok(m),
+                    _ => return Err(format!("Merge key [{}] array elements must be maps", YAML_MERGE_KEY)),
+                }).collect::<Result<Vec<_>, _>>()?,
+                _ => return Err(format!("Merge key [{}] value must be a map or an array of maps", YAML_MERGE_KEY)),
+            };
+            for m in merge {
+                for (k, v) in m.value {
+                    if value.contains_key(&k) {
+                        continue;
+                    }
+                    if let SupervalueMapType::ComplexKeys(complex) = &m.type_ {
+                        if complex.contains(&k) {
+                            match &mut type_ {
+                                SupervalueMapType::ComplexKeys(dest) => {
+                                    dest.insert(k.clone());
+                                },
+                                _ => {
+                                    type_ = SupervalueMapType::ComplexKeys([k.clone()].into_iter().collect());
+                                },
+                            }
+                        }
+                    }
+                    value.insert(k, v);
+                }
+            }
+            return Ok(Supervalue::Map(SupervalueMap {
+                type_: type_,
+                value: value,
+            }));
+        },
+        Supervalue::Vec(mut vec) => {
+            vec.value = vec.value.into_iter().map(resolve_merge_keys).collect::<Result<_, _>>()?;
+            return Ok(Supervalue::Vec(vec));
+        },
+        v => return Ok(v),
+    }
+}
+
+/// An anchor in a YAML document, with the paths of the anchored node and each
+/// alias to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct YamlAnchor {
+    pub name: String,
+    /// The anchored node as parsed.
+    pub value: Supervalue,
+    /// The anchored node's path followed by the alias paths, in document order.
+    pub paths: Vec<DataPath>,
+}
+
+/// The state of a collection in the event stream, with the path of the
+/// collection (`None` if it's within a map key).
+enum Frame {
+    Seq {
+        path: Option<Vec<serde_json::Value>>,
+        next: usize,
+    },
+    Map {
+        path: Option<Vec<serde_json::Value>>,
+        /// `Some` after the key is parsed, `Some(None)` if it's not a string
+        key: Option<Option<String>>,
+    },
+}
+
+enum Event {
+    End,
+    Other,
+    CollectionEnd,
+    Alias(String),
+    Scalar {
+        anchor: Option<String>,
+        tagged: bool,
+        plain: bool,
+        value: String,
+        /// Where the scalar starts and ends, as (line, column)
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    CollectionStart {
+        seq: bool,
+        anchor: Option<String>,
+        tagged: bool,
+    },
+}
+
+/// A libyaml event parser reading directly from `text`.
+struct Parser<'a> {
+    sys: Box<MaybeUninit<sys::yaml_parser_t>>,
+    _text: PhantomData<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        let mut sys = Box::new(MaybeUninit::<sys::yaml_parser_t>::uninit());
+        unsafe {
+            if sys::yaml_parser_initialize(sys.as_mut_ptr()).fail {
+                panic!("Error allocating YAML parser");
+            }
+            sys::yaml_parser_set_encoding(sys.as_mut_ptr(), sys::YAML_UTF8_ENCODING);
+            sys::yaml_parser_set_input_string(sys.as_mut_ptr(), text.as_ptr(), text.len() as u64);
+        }
+        return Parser {
+            sys: sys,
+            _text: PhantomData,
+        };
+    }
+
+    fn next(&mut self) -> Result<Event, String> {
+        unsafe fn string(p: *const u8) -> Option<String> {
+            if p.is_null() {
+                return None;
+            }
+            return Some(unsafe { CStr::from_ptr(p as *const _) }.to_string_lossy().to_string());
+        }
+
+        unsafe {
+            let parser = self.sys.as_mut_ptr();
+            let mut event = MaybeUninit::<sys::yaml_event_t>::uninit();
+            if sys::yaml_parser_parse(parser, event.as_mut_ptr()).fail {
+                let parser = &*parser;
+                return Err(
+                    format!(
+                        "{} at line {} column {}",
+                        string(parser.problem as *const u8).unwrap_or_default(),
+                        parser.problem_mark.line + 1,
+                        parser.problem_mark.column + 1
+                    ),
+                );
+            }
+            let e = &*event.as_ptr();
+            let out = match e.type_ {
+                sys::YAML_STREAM_END_EVENT | sys::YAML_DOCUMENT_END_EVENT => Event::End,
+                sys::YAML_ALIAS_EVENT => Event::Alias(string(e.data.alias.anchor).unwrap_or_default()),
+                sys::YAML_SCALAR_EVENT => Event::Scalar {
+                    anchor: string(e.data.scalar.anchor),
+                    tagged: !e.data.scalar.tag.is_null(),
+                    plain: e.data.scalar.style == sys::YAML_PLAIN_SCALAR_STYLE,
+                    value: String::from_utf8_lossy(
+                        std::slice::from_raw_parts(e.data.scalar.value, e.data.scalar.length as usize),
+                    ).to_string(),
+                    start: (e.start_mark.line as usize, e.start_mark.column as usize),
+                    end: (e.end_mark.line as usize, e.end_mark.column as usize),
+                },
+                sys::YAML_SEQUENCE_START_EVENT => Event::CollectionStart {
+                    seq: true,
+                    anchor: string(e.data.sequence_start.anchor),
+                    tagged: !e.data.sequence_start.tag.is_null(),
+                },
+                sys::YAML_MAPPING_START_EVENT => Event::CollectionStart {
+                    seq: false,
+                    anchor: string(e.data.mapping_start.anchor),
+                    tagged: !e.data.mapping_start.tag.is_null(),
+                },
+                sys::YAML_SEQUENCE_END_EVENT | sys::YAML_MAPPING_END_EVENT => Event::CollectionEnd,
+                _ => Event::Other,
+            };
+            sys::yaml_event_delete(event.as_mut_ptr());
+            return Ok(out);
+        }
+    }
+}
+
+impl<'a> Drop for Parser<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::yaml_parser_delete(self.sys.as_mut_ptr());
+        }
+    }
+}
+
+/// The (zero based) lines within scalars spanning multiple lines, like block
+/// scalars, which can't contain comments.
+pub fn yaml_scalar_lines(text: &str) -> Result<HashSet<usize>, String> {
+    let mut out = HashSet::new();
+    let mut parser = Parser::new(text);
+    loop {
+        match parser.next()? {
+            Event::End => break,
+            Event::Scalar { start, end, .. } => {
+                // The scalar ends at the start of the line after it if there are trailing
+                // line breaks
+                let last = if end.1 == 0 {
+                    end.0.saturating_sub(1)
+                } else {
+                    end.0
+                };
+                out.extend(start.0 + 1 ..= last);
+            },
+            _ => { },
+        }
+    }
+    return Ok(out);
+}
+
+/// Find the anchors in (the first document of) a YAML document, given the parsed
+/// `value`. Anchors on tagged nodes and anchors or aliases within complex map
+/// keys (or under keys that aren't strings) are ignored.
+pub fn find_yaml_anchors(text: &str, value: &Supervalue) -> Result<Vec<YamlAnchor>, String> {
+    let mut out: Vec<YamlAnchor> = vec![];
+    let mut names = HashMap::new();
+    let mut stack: Vec<Frame> = vec![];
+    let mut parser = Parser::new(text);
+    loop {
+        let event = parser.next()?;
+        let (anchor, tagged) = match &event {
+            Event::End => break,
+            Event::Other => continue,
+            Event::CollectionEnd => {
+                stack.pop();
+                continue;
+            },
+            Event::Alias(_) => (None, false),
+            Event::Scalar { anchor, tagged, .. } => (anchor.clone(), *tagged),
+            Event::CollectionStart { anchor, tagged, .. } => (anchor.clone(), *tagged),
+        };
+
+        // Determine the path of this node
+        let path = match stack.last_mut() {
+            None => Some(vec![]),
+            Some(Frame::Seq { path, next }) => {
+                let out = path.clone().map(|mut p| {
+                    p.push(serde_json::Value::from(*next));
+                    p
+                });
+                *next += 1;
+                out
+            },
+            Some(Frame::Map { path, key }) => match key.take() {
+                Some(key) => match (path, key) {
+                    (Some(path), Some(key)) => {
+                        let mut p = path.clone();
+                        p.push(serde_json::Value::String(key));
+                        Some(p)
+                    },
+                    _ => None,
+                },
+                None => {
+                    // This node is a key
+                    *key = Some(match &event {
+                        Event::Scalar { tagged: false, plain, value, .. } => {
+                            if !*plain ||
+                                matches!(
+                                    serde_yaml::from_str::<serde_yaml::Value>(value),
+                                    Ok(serde_yaml::Value::String(_))
+                                ) {
+                                Some(value.clone())
+                            } else {
+                                None
+                            }
+                        },
+                        _ => None,
+                    });
+                    None
+                },
+            },
+        };
+
+        // Record anchors and aliases
+        match &event {
+            Event::Alias(name) => {
+                if let (Some(path), Some(i)) = (&path, names.get(name)) {
+                    let anchor: &mut YamlAnchor = &mut out[*i];
+                    anchor.paths.push(DataPath(path.clone()));
+                }
+            },
+            _ => {
+                if let Some(name) = anchor {
+                    names.remove(&name);
+                    if let (Some(path), false) = (&path, tagged) {
+                        let path = DataPath(path.clone());
+                        if let Ok(v) = value.get_path(&path) {
+                            names.insert(name.clone(), out.len());
+                            out.push(YamlAnchor {
+                                name: name,
+                                value: v.clone(),
+                                paths: vec![path],
+                            });
+                        }
+                    }
+                }
+            },
+        }
+        if let Event::CollectionStart { seq, .. } = event {
+            if seq {
+                stack.push(Frame::Seq {
+                    path: path,
+                    next: 0,
+                });
+            } else {
+                stack.push(Frame::Map {
+                    path: path,
+                    key: None,
+                });
+            }
+        }
+    }
+    return Ok(out);
+}
+
+fn yaml_at_path<'a>(mut at: &'a mut serde_yaml::Value, path: &DataPath) -> Option<&'a mut serde_yaml::Value> {
+    for seg in &path.0 {
+        while let serde_yaml::Value::Tagged(t) = at {
+            at = &mut t.value;
+        }
+        match (at, seg) {
+            (serde_yaml::Value::Mapping(m), serde_json::Value::String(k)) => {
+                at = m.get_mut(k.as_str())?;
+            },
+            (serde_yaml::Value::Sequence(s), serde_json::Value::Number(i)) => {
+                at = s.get_mut(i.as_u64()? as usize)?;
+            },
+            _ => return None,
+        }
+    }
+    return Some(at);
+}
+
+fn tag_at_path(value: &mut serde_yaml::Value, path: &DataPath, tag: String) {
+    if let Some(at) = yaml_at_path(value, path) {
+        let inner = std::mem::replace(at, serde_yaml::Value::Null);
+        *at = serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+            tag: serde_yaml::value::Tag::new(tag),
+            value: inner,
+        }));
+    }
+}
+
+/// Write data as YAML, restoring `anchors` (from `find_yaml_anchors`) where the
+/// anchored node and aliases are unchanged. Since the order of map keys may
+/// differ from the source, the anchor is placed on whichever unchanged node is
+/// written first. If the anchors can't be restored the data is written without
+/// them.
+pub fn to_yaml_string_with_anchors(
+    value: &Supervalue,
+    anchors: &[YamlAnchor],
+    options: &ConvertOptions,
+) -> Result<String, String> {
+    let plain = value.clone().to_yaml_value(options)?;
+    let plain_text = serde_yaml::to_string(&plain).map_err(|e| e.to_string())?;
+    if plain_text.contains("hoj.anchor.") || plain_text.contains("hoj.alias.") {
+        return Ok(plain_text);
+    }
+
+    // Find unchanged nodes
+    let mut kept = vec![];
+    for (i, anchor) in anchors.iter().enumerate() {
+        if let Supervalue::Map(m) = &anchor.value {
+            if m.as_tagged().is_some() {
+                continue;
+            }
+        }
+        let paths =
+            anchor.paths.iter().filter(|p| value.get_path(p).is_ok_and(|v| *v == anchor.value)).collect::<Vec<_>>();
+        if paths.len() < 2 {
+            continue;
+        }
+        kept.push((i, paths));
+    }
+    if kept.is_empty() {
+        return Ok(plain_text);
+    }
+
+    // Find which node of each anchor is written first
+    let mut probe = plain.clone();
+    for (i, paths) in &kept {
+        for (j, path) in paths.iter().enumerate() {
+            tag_at_path(&mut probe, path, format!("hoj.anchor.{}.{}.", i, j));
+        }
+    }
+    let probe_text = serde_yaml::to_string(&probe).map_err(|e| e.to_string())?;
+    let mut out = plain.clone();
+    let mut replacements = vec![];
+    for (i, paths) in &kept {
+        let Some((first, _)) =
+            paths
+                .iter()
+                .enumerate()
+                .filter_map(|(j, _)| probe_text.find(&format!("!hoj.anchor.{}.{}.", i, j)).map(|pos| (j, pos)))
+                .min_by_key(|(_, pos)| *pos) else {
+                continue;
+            };
+        for (j, path) in paths.iter().enumerate() {
+            if j == first {
+                tag_at_path(&mut out, path, format!("hoj.anchor.{}.", i));
+            } else if let Some(at) = yaml_at_path(&mut out, path) {
+                *at = serde_yaml::Value::String(format!("hoj.alias.{}.", i));
+            }
+        }
+        replacements.push((format!("!hoj.anchor.{}.", i), format!("&{}", anchors[*i].name)));
+        replacements.push((format!("hoj.alias.{}.", i), format!("*{}", anchors[*i].name)));
+    }
+    let mut text = serde_yaml::to_string(&out).map_err(|e| e.to_string())?;
+    for (placeholder, replacement) in replacements {
+        text = text.replace(&placeholder, &replacement);
+    }
+
+    // Make sure nothing was lost
+    match serde_yaml::from_str::<serde_yaml::Value>(&text) {
+        Ok(v) if v == plain => return Ok(text),
+        _ => return Ok(plain_text),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            find_yaml_anchors,
+            resolve_merge_keys,
+            to_yaml_string_with_anchors,
+        },
+        crate::supervalue::{
+            ConvertOptions,
+            Supervalue,
+        },
+        serde_json::json,
+    };
+
+    fn yaml(text: &str) -> Supervalue {
+        return Supervalue::from(serde_yaml::from_str::<serde_yaml::Value>(text).unwrap());
+    }
+
+    #[test]
+    fn merge_keys() {
+        let source = yaml("base: &b {a: 1, b: 2}\nother: {c: 3}\nx:\n  <<: [*b, {a: 9, d: 4}]\n  b: 5\ny:\n  <<: *b\n");
+        assert_eq!(resolve_merge_keys(source).unwrap(), Supervalue::from(json!({
+            "base": {
+                "a": 1,
+                "b": 2
+            },
+            "other": {
+                "c": 3
+            },
+            "x": {
+                "a": 1,
+                "b": 5,
+                "d": 4
+            },
+            "y": {
+                "a": 1,
+                "b": 2
+            },
+        })));
+        assert!(resolve_merge_keys(yaml("x:\n  <<: 1\n")).is_err());
+    }
+
+    #[test]
+    fn anchors() {
+        let text = "base: &b\n  a: 1\nx:\n  <<: *b\n  c: 2\ny: *b\nz: [*b]\n";
+        let mut value = yaml(text);
+        let anchors = find_yaml_anchors(text, &value).unwrap();
+        assert_eq!(anchors.len(), 1);
+        assert_eq!(anchors[0].paths.len(), 4);
+        let out = to_yaml_string_with_anchors(&value, &anchors, &ConvertOptions::default()).unwrap();
+        assert_eq!(out.matches("&b").count(), 1);
+        assert_eq!(out.matches("*b").count(), 3);
+        assert_eq!(yaml(&out), value);
+
+        // Modified nodes are written in full
+        let Supervalue::Map(m) = &mut value else {
+            unreachable!();
+        };
+        m.value.insert("y".to_string(), Supervalue::from(json!({
+            "a": 2
+        })));
+        let out = to_yaml_string_with_anchors(&value, &anchors, &ConvertOptions::default()).unwrap();
+        assert_eq!(out.matches("&b").count(), 1);
+        assert_eq!(out.matches("*b").count(), 2);
+        assert_eq!(yaml(&out), value);
+    }
+}
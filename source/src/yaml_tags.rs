@@ -0,0 +1,119 @@
+use {
+    crate::supervalue::{
+        Supervalue,
+        SupervalueMap,
+        SupervalueMapType,
+    },
+};
+
+/// How tagged values are represented in formats without tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagStyle {
+    /// A map with the tag and value: `{"tag": "!Ref", "value": "foo"}`
+    #[default]
+    Map,
+    /// A single key map with the tag as the key: `{"!Ref": "foo"}`
+    Key,
+    /// Just the value, dropping the tag: `"foo"`
+    Value,
+}
+
+/// Recursively replace tagged values with the representation for `style`.
+pub fn tags_to_style(source: Supervalue, style: TagStyle) -> Supervalue {
+    match source {
+        Supervalue::Map(map) => {
+            let map = SupervalueMap {
+                type_: map.type_,
+                value: map.value.into_iter().map(|(k, v)| (k, tags_to_style(v, style))).collect(),
+            };
+            let Some((tag, value)) = map.as_tagged() else {
+                return Supervalue::Map(map);
+            };
+            match style {
+                TagStyle::Map => return Supervalue::Map(SupervalueMap {
+                    type_: SupervalueMapType::Normal,
+                    value: map.value,
+                }),
+                TagStyle::Key => return Supervalue::Map(SupervalueMap {
+                    type_: SupervalueMapType::Normal,
+                    value: [(tag.to_string(), value.clone())].into_iter().collect(),
+                }),
+                TagStyle::Value => return value.clone(),
+            }
+        },
+        Supervalue::Vec(mut vec) => {
+            vec.value = vec.value.into_iter().map(|v| tags_to_style(v, style)).collect();
+            return Supervalue::Vec(vec);
+        },
+        v => return v,
+    }
+}
+
+/// Reverse `TagStyle::Key`, recursively replacing maps with a single key starting
+/// with `!` (like `{"!Ref": "foo"}`) with tagged values.
+pub fn tags_from_keys(source: Supervalue) -> Supervalue {
+    match source {
+        Supervalue::Map(map) => {
+            let mut value =
+                map.value.into_iter().map(|(k, v)| (k, tags_from_keys(v))).collect::<Vec<_>>();
+            if value.len() == 1 && value[0].0.starts_with("!") && value[0].0.len() > 1 {
+                let (tag, value) = value.pop().unwrap();
+                return Supervalue::Map(SupervalueMap::new_tagged(tag, value));
+            }
+            return Supervalue::Map(SupervalueMap {
+                type_: map.type_,
+                value: value.into_iter().collect(),
+            });
+        },
+        Supervalue::Vec(mut vec) => {
+            vec.value = vec.value.into_iter().map(tags_from_keys).collect();
+            return Supervalue::Vec(vec);
+        },
+        v => return v,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            tags_from_keys,
+            tags_to_style,
+            TagStyle,
+        },
+        crate::supervalue::Supervalue,
+        serde_json::json,
+    };
+
+    #[test]
+    fn styles() {
+        let source =
+            Supervalue::from(
+                serde_yaml::from_str::<serde_yaml::Value>("a: !Ref foo\nb: [!Sub 'x-${y}']\n").unwrap(),
+            );
+        assert_eq!(tags_to_style(source.clone(), TagStyle::Map), Supervalue::from(json!({
+            "a": {
+                "tag": "!Ref",
+                "value": "foo"
+            },
+            "b": [{
+                "tag": "!Sub",
+                "value": "x-${y}"
+            }],
+        })));
+        let keys = tags_to_style(source.clone(), TagStyle::Key);
+        assert_eq!(keys, Supervalue::from(json!({
+            "a": {
+                "!Ref": "foo"
+            },
+            "b": [{
+                "!Sub": "x-${y}"
+            }],
+        })));
+        assert_eq!(tags_from_keys(keys), source);
+        assert_eq!(tags_to_style(source.clone(), TagStyle::Value), Supervalue::from(json!({
+            "a": "foo",
+            "b": ["x-${y}"],
+        })));
+    }
+}